@@ -1,7 +1,131 @@
-use rocket::{catch, request::Request};
+use rocket::{
+    catch,
+    http::{Header, Status},
+    request::Request,
+    response::{self, Responder, Response},
+};
+use serde::Serialize;
+
+use crate::{
+    guards::{
+        api_key::{ApiKeyError, ApiKeyFailure},
+        twitch_auth::{AccessTokenError, AccessTokenFailure},
+    },
+    utils::JsonResponse,
+};
+
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    error: &'static str,
+    message: &'static str,
+}
+
+pub struct ErrorResponse {
+    body: ErrorBody,
+    status: Status,
+    headers: Vec<Header<'static>>,
+}
+
+impl ErrorResponse {
+    fn new(status: Status, error: &'static str, message: &'static str) -> Self {
+        Self {
+            body: ErrorBody { error, message },
+            status,
+            headers: vec![],
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push(Header::new(name, value));
+        self
+    }
+
+    fn from_request(req: &Request, status: Status) -> Self {
+        if let Some(error) = req.local_cache(|| ApiKeyFailure(None)).0 {
+            return Self::from_api_key_error(error);
+        }
+
+        let access_token_error = req.local_cache(|| AccessTokenFailure(None)).0;
+        let response = match access_token_error {
+            Some(error) => Self::new(status, error.code(), error.message()),
+            None => Self::new(
+                status,
+                match status.code {
+                    403 => "forbidden",
+                    429 => "rate_limited",
+                    503 => "unavailable",
+                    _ => "unauthorized",
+                },
+                status.reason_lossy(),
+            ),
+        };
+
+        match status.code {
+            401 | 403 => response.header("WWW-Authenticate", challenge(access_token_error)),
+            _ => response,
+        }
+    }
+
+    fn from_api_key_error(error: ApiKeyError) -> Self {
+        let response = Self::new(error.status(), error.code(), error.message());
+
+        match error {
+            ApiKeyError::RateLimited { retry_after } => {
+                response.header("Retry-After", retry_after.to_string())
+            }
+            _ => response,
+        }
+    }
+}
+
+fn challenge(error: Option<AccessTokenError>) -> String {
+    let mut challenge = "Bearer realm=\"stream-collection\"".to_owned();
+
+    if let Some(error) = error {
+        if let Some(oauth_error) = error.oauth_error() {
+            challenge.push_str(&format!(
+                ", error=\"{}\", error_description=\"{}\"",
+                oauth_error,
+                error.message()
+            ));
+        }
+    }
+
+    challenge
+}
+
+impl<'r> Responder<'r, 'static> for ErrorResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response =
+            Response::build_from(JsonResponse::new(self.body, self.status).respond_to(request)?);
+
+        for header in self.headers {
+            response.header(header);
+        }
+
+        response.ok()
+    }
+}
 
 #[catch(404)]
 pub fn not_found(_: &Request) {}
 
 #[catch(401)]
-pub fn unauthorized(_: &Request) {}
+pub fn unauthorized(req: &Request) -> ErrorResponse {
+    ErrorResponse::from_request(req, Status::Unauthorized)
+}
+
+#[catch(403)]
+pub fn forbidden(req: &Request) -> ErrorResponse {
+    ErrorResponse::from_request(req, Status::Forbidden)
+}
+
+#[catch(429)]
+pub fn too_many_requests(req: &Request) -> ErrorResponse {
+    ErrorResponse::from_request(req, Status::TooManyRequests)
+}
+
+#[catch(503)]
+pub fn service_unavailable(req: &Request) -> ErrorResponse {
+    ErrorResponse::from_request(req, Status::ServiceUnavailable)
+}
@@ -1,8 +1,8 @@
 use rocket::FromFormField;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Eq, PartialEq, Hash, FromFormField, Serialize, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, FromFormField, Serialize, Deserialize, Clone)]
 pub enum Category {
     Programming,
     WebDevelopment,
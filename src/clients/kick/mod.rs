@@ -0,0 +1,212 @@
+use std::time::Instant;
+
+use isahc::{
+    http::{header::AUTHORIZATION, StatusCode},
+    AsyncBody, AsyncReadResponseExt, Request, Response,
+};
+use rocket::http::{RawStr, Status};
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{debug, debug_span, info, warn, Instrument};
+
+use crate::metrics::{KICK_REQUESTS, KICK_REQUEST_DURATION};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct KickToken {
+    pub access_token: String,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickResponse<T> {
+    #[serde(default = "Vec::new")]
+    pub data: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickCategory {
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickLivestream {
+    pub broadcaster_user_id: u64,
+    pub slug: String,
+    pub stream_title: String,
+    #[serde(default)]
+    pub language: String,
+    pub viewer_count: u64,
+    #[serde(default)]
+    pub thumbnail: String,
+    #[serde(default)]
+    pub started_at: String,
+    pub category: KickCategory,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickChannel {
+    pub broadcaster_user_id: u64,
+    pub slug: String,
+    #[serde(default)]
+    pub channel_description: String,
+    #[serde(default)]
+    pub banner_picture: String,
+    #[serde(default)]
+    pub stream_title: String,
+    pub category: Option<KickCategory>,
+    pub stream: Option<KickChannelStream>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KickChannelStream {
+    pub is_live: bool,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub start_time: String,
+    #[serde(default)]
+    pub thumbnail: String,
+    #[serde(default)]
+    pub viewer_count: u64,
+}
+
+fn encode(value: &str) -> String {
+    RawStr::new(value).percent_encode().to_string()
+}
+
+fn record_request<T>(
+    endpoint: &str,
+    response: &Result<Response<T>, isahc::Error>,
+    started_at: Instant,
+) {
+    let status = match response {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "error".to_owned(),
+    };
+    debug!(
+        endpoint,
+        status = %status,
+        duration_ms = started_at.elapsed().as_millis() as u64,
+        "kick request finished"
+    );
+    KICK_REQUESTS.with_label_values(&[endpoint, &status]).inc();
+    KICK_REQUEST_DURATION
+        .with_label_values(&[endpoint, &status])
+        .observe(started_at.elapsed().as_secs_f64());
+}
+
+/// Fetches an app access token with the client credentials grant.
+pub async fn get_token(
+    auth_url: &str,
+    client_id: &str,
+    client_secret: &str,
+) -> Result<KickToken, Status> {
+    let body = format!(
+        "grant_type=client_credentials&client_id={}&client_secret={}",
+        encode(client_id),
+        encode(client_secret)
+    );
+    let request = Request::post(format!("{}/oauth/token", auth_url))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .map_err(|_| Status::InternalServerError)?;
+
+    let started_at = Instant::now();
+    let response = isahc::send_async(request).await;
+    record_request("oauth/token", &response, started_at);
+
+    let mut response = response.map_err(|e| {
+        warn!(error = %e, "kick app token request failed");
+        Status::ServiceUnavailable
+    })?;
+
+    if response.status() != StatusCode::OK {
+        warn!(status = %response.status(), "kick app token request rejected");
+        return Err(Status::BadGateway);
+    }
+
+    response.json().await.map_err(|_| Status::BadGateway)
+}
+
+/// Sends a request to Kick's public API, recording its status and duration.
+async fn send(
+    endpoint: &str,
+    url: String,
+    access_token: Option<&str>,
+) -> Result<Response<AsyncBody>, isahc::Error> {
+    let mut request = Request::get(url);
+    if let Some(access_token) = access_token {
+        request = request.header(AUTHORIZATION, format!("Bearer {}", access_token));
+    }
+    let request = request.body(()).map_err(isahc::Error::from)?;
+
+    let started_at = Instant::now();
+    let response = isahc::send_async(request)
+        .instrument(debug_span!("kick_request", endpoint))
+        .await;
+    record_request(endpoint, &response, started_at);
+
+    response
+}
+
+async fn get_json<T: DeserializeOwned + Unpin>(
+    endpoint: &str,
+    url: String,
+    access_token: Option<&str>,
+) -> Result<KickResponse<T>, Status> {
+    let mut response = send(endpoint, url, access_token)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    match response.status() {
+        StatusCode::OK => response.json().await.map_err(|_| Status::BadGateway),
+        StatusCode::UNAUTHORIZED => {
+            info!(
+                endpoint,
+                "kick request unauthorized, check the kick client credentials"
+            );
+            Err(Status::Unauthorized)
+        }
+        StatusCode::TOO_MANY_REQUESTS => Err(Status::TooManyRequests),
+        StatusCode::NOT_FOUND => Err(Status::NotFound),
+        status => {
+            info!(endpoint, %status, "kick request failed");
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+/// Searches categories by name.
+pub async fn search_categories(
+    base_url: &str,
+    access_token: Option<&str>,
+    name: &str,
+) -> Result<KickResponse<KickCategory>, Status> {
+    let url = format!("{}/public/v1/categories?q={}", base_url, encode(name));
+
+    get_json("categories", url, access_token).await
+}
+
+/// The most watched live streams in a category. Kick doesn't paginate this endpoint.
+pub async fn get_category_livestreams(
+    base_url: &str,
+    access_token: Option<&str>,
+    category_id: u64,
+) -> Result<KickResponse<KickLivestream>, Status> {
+    let url = format!(
+        "{}/public/v1/livestreams?category_id={}&limit=100&sort=viewer_count",
+        base_url, category_id
+    );
+
+    get_json("livestreams", url, access_token).await
+}
+
+pub async fn get_channel(
+    base_url: &str,
+    access_token: Option<&str>,
+    slug: &str,
+) -> Result<KickResponse<KickChannel>, Status> {
+    let url = format!("{}/public/v1/channels?slug={}", base_url, encode(slug));
+
+    get_json("channels", url, access_token).await
+}
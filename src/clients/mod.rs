@@ -1 +1,3 @@
+pub mod kick;
 pub mod twitch;
+pub mod youtube;
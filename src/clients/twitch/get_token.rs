@@ -1,19 +1,58 @@
-use isahc::ReadResponseExt;
+use std::time::Instant;
+
+use isahc::{http::StatusCode, AsyncReadResponseExt, Response};
+use rocket::http::Status;
 use serde::Deserialize;
+use tracing::warn;
+
+use super::{record_request, Bucket};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct Token {
     pub access_token: String,
     pub expires_in: u64,
-    pub token_type: String,
 }
 
-pub fn get_token(client_id: &str, client_secret: &str) -> Token {
-    let url = format!(
+fn token_url(client_id: &str, client_secret: &str) -> String {
+    format!(
         "https://id.twitch.tv/oauth2/token?client_id={}&client_secret={}&grant_type={}",
         client_id, client_secret, "client_credentials"
+    )
+}
+
+/// Twitch answers 400 for an unknown client id and 403 for a wrong secret; both come back as
+/// `Unauthorized`, unlike outages, which are worth retrying.
+fn check_response<T>(response: Result<Response<T>, isahc::Error>) -> Result<Response<T>, Status> {
+    let response = response.map_err(|e| {
+        warn!(error = %e, "app token request failed");
+        Status::ServiceUnavailable
+    })?;
+
+    match response.status() {
+        StatusCode::OK => Ok(response),
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            warn!(status = %response.status(), "twitch rejected the app credentials");
+            Err(Status::Unauthorized)
+        }
+        status => {
+            warn!(%status, "app token request rejected");
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+pub async fn get_token(client_id: &str, client_secret: &str) -> Result<Token, Status> {
+    let started_at = Instant::now();
+    let response = isahc::post_async(token_url(client_id, client_secret), "").await;
+    record_request(
+        "oauth2/token",
+        Bucket::Other,
+        &response,
+        started_at.elapsed(),
     );
 
-    let mut response = isahc::post(url, "").unwrap();
-    response.json().unwrap()
+    check_response(response)?
+        .json()
+        .await
+        .map_err(|_| Status::BadGateway)
 }
@@ -1,8 +1,12 @@
 mod get_token;
+mod oauth;
+mod send;
 pub mod streams;
-pub mod user;
 pub mod tags;
+pub mod user;
 
 pub use get_token::*;
+pub use oauth::*;
+pub use send::*;
 pub use streams::*;
 pub use tags::*;
@@ -0,0 +1,93 @@
+use isahc::{http::StatusCode, AsyncReadResponseExt, Request};
+use rocket::{http::RawStr, http::Status};
+use serde::Deserialize;
+use tracing::info;
+
+use super::{send, Bucket};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct UserToken {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: u64,
+}
+
+fn encode(value: &str) -> String {
+    RawStr::new(value).percent_encode().to_string()
+}
+
+pub fn get_authorize_url(
+    client_id: &str,
+    redirect_uri: &str,
+    scopes: &[String],
+    state: &str,
+    code_challenge: &str,
+) -> String {
+    format!(
+        "https://id.twitch.tv/oauth2/authorize?response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&code_challenge={}&code_challenge_method=S256",
+        encode(client_id),
+        encode(redirect_uri),
+        encode(&scopes.join(" ")),
+        encode(state),
+        encode(code_challenge)
+    )
+}
+
+async fn request_user_token(form: String) -> Result<UserToken, Status> {
+    let request = Request::builder()
+        .uri("https://id.twitch.tv/oauth2/token")
+        .method("POST")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(form)
+        .map_err(|_| Status::InternalServerError)?;
+
+    let mut response = send("oauth2/token", Bucket::Other, request)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    match response.status() {
+        StatusCode::OK => response.json().await.map_err(|_| Status::BadGateway),
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => {
+            info!(status = %response.status(), "user token request rejected");
+            Err(Status::Unauthorized)
+        }
+        status => {
+            info!(%status, "user token request failed");
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+pub async fn exchange_code(
+    client_id: &str,
+    client_secret: &str,
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: &str,
+) -> Result<UserToken, Status> {
+    let form = format!(
+        "client_id={}&client_secret={}&code={}&grant_type=authorization_code&redirect_uri={}&code_verifier={}",
+        encode(client_id),
+        encode(client_secret),
+        encode(code),
+        encode(redirect_uri),
+        encode(code_verifier)
+    );
+
+    request_user_token(form).await
+}
+
+pub async fn refresh_user_token(
+    client_id: &str,
+    client_secret: &str,
+    refresh_token: &str,
+) -> Result<UserToken, Status> {
+    let form = format!(
+        "client_id={}&client_secret={}&grant_type=refresh_token&refresh_token={}",
+        encode(client_id),
+        encode(client_secret),
+        encode(refresh_token)
+    );
+
+    request_user_token(form).await
+}
@@ -0,0 +1,79 @@
+use std::time::{Duration, Instant};
+
+use isahc::{AsyncBody, Request, Response};
+use tracing::{debug, debug_span, Instrument};
+
+use crate::metrics::{
+    TWITCH_RATE_LIMIT_REMAINING, TWITCH_RATE_LIMIT_RESET, TWITCH_REQUESTS, TWITCH_REQUEST_DURATION,
+};
+
+fn header_i64<T>(response: &Response<T>, name: &str) -> Option<i64> {
+    response
+        .headers()
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse::<i64>()
+        .ok()
+}
+
+/// The rate limit bucket a request draws from. Helix gives each user token a bucket of its
+/// own, so only requests made with the app token say how much budget the poller has left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bucket {
+    App,
+    /// User token requests, and id.twitch.tv, which isn't rate limited like Helix.
+    Other,
+}
+
+pub fn record_request<T>(
+    endpoint: &str,
+    bucket: Bucket,
+    response: &Result<Response<T>, isahc::Error>,
+    elapsed: Duration,
+) {
+    let status = match response {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "error".to_owned(),
+    };
+
+    debug!(
+        endpoint,
+        status = %status,
+        duration_ms = elapsed.as_millis() as u64,
+        "twitch request finished"
+    );
+
+    TWITCH_REQUESTS
+        .with_label_values(&[endpoint, &status])
+        .inc();
+    TWITCH_REQUEST_DURATION
+        .with_label_values(&[endpoint, &status])
+        .observe(elapsed.as_secs_f64());
+
+    if let (Ok(response), Bucket::App) = (response, bucket) {
+        if let Some(remaining) = header_i64(response, "Ratelimit-Remaining") {
+            TWITCH_RATE_LIMIT_REMAINING.set(remaining);
+        }
+        if let Some(reset) = header_i64(response, "Ratelimit-Reset") {
+            TWITCH_RATE_LIMIT_RESET.set(reset);
+        }
+    }
+}
+
+/// Sends a request to Twitch, recording its status, duration and, for app token requests,
+/// the rate limit budget left.
+pub async fn send<B: Into<AsyncBody>>(
+    endpoint: &str,
+    bucket: Bucket,
+    request: Request<B>,
+) -> Result<Response<AsyncBody>, isahc::Error> {
+    let started_at = Instant::now();
+    let response = isahc::send_async(request)
+        .instrument(debug_span!("twitch_request", endpoint))
+        .await;
+
+    record_request(endpoint, bucket, &response, started_at.elapsed());
+
+    response
+}
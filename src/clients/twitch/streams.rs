@@ -1,6 +1,13 @@
-use isahc::{AsyncReadResponseExt, Request, Response, AsyncBody};
-use rocket::{info, serde::json::serde_json::to_string};
+use isahc::{http::StatusCode, AsyncReadResponseExt, Request};
+use rocket::http::{RawStr, Status};
 use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use super::{send, Bucket};
+
+fn encode(value: &str) -> String {
+    RawStr::new(value).percent_encode().to_string()
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TwitchStream {
@@ -30,89 +37,79 @@ pub struct TwitchStreamsResponse {
     pub pagination: TwitchPagination,
 }
 
-pub async fn get_science_and_tech_streams(
+pub async fn get_game_streams(
     twitch_client_id: &str,
     access_token: &str,
+    game_id: &str,
     after: &str,
-) -> TwitchStreamsResponse {
+) -> Result<TwitchStreamsResponse, Status> {
     let after_query = match after.is_empty() {
         true => after.to_owned(),
         false => format!("&after={}", after),
     };
 
     let url = format!(
-        "https://api.twitch.tv/helix/streams?game_id=509670&first=100{}",
-        after_query
+        "https://api.twitch.tv/helix/streams?game_id={}&first=100{}",
+        game_id, after_query
     );
 
-    info!("requesting url {}", url);
+    debug!(%url, "requesting streams");
 
-    let streams = fetch_programming_streams(twitch_client_id, access_token, url).await;
+    let streams = fetch_programming_streams(twitch_client_id, access_token, url).await?;
 
-    info!("fetched first stream {:?}", streams.data.get(0));
-    info!("fetched {} streams", streams.data.len());
+    debug!(streams = streams.data.len(), "fetched streams");
 
-    streams
+    Ok(streams)
 }
 
-pub async fn get_software_game_dev_streams(
+/// Live streams of up to 100 broadcasters, whatever they are streaming.
+pub async fn get_user_streams(
     twitch_client_id: &str,
     access_token: &str,
-    after: &str,
-) -> TwitchStreamsResponse {
-    let after_query = match after.is_empty() {
-        true => after.to_owned(),
-        false => format!("&after={}", after),
-    };
+    user_ids: &[String],
+) -> Result<TwitchStreamsResponse, Status> {
+    let user_query: Vec<String> = user_ids
+        .iter()
+        .map(|id| format!("user_id={}", encode(id)))
+        .collect();
 
     let url = format!(
-        "https://api.twitch.tv/helix/streams?game_id=1469308723&first=100{}",
-        after_query
+        "https://api.twitch.tv/helix/streams?{}&first=100",
+        user_query.join("&")
     );
 
-    info!("requesting url {}", url);
-
-    let streams = fetch_programming_streams(twitch_client_id, access_token, url).await;
-
-    info!("fetched first stream {:?}", streams.data.get(0));
-    info!("fetched {} streams", streams.data.len());
+    debug!(users = user_ids.len(), "requesting user streams");
 
-    streams
+    fetch_programming_streams(twitch_client_id, access_token, url).await
 }
 
+/// Fails on anything but a page of streams, so the poller can tell a failed request from a
+/// category nobody is streaming in.
 pub async fn fetch_programming_streams(
     twitch_client_id: &str,
     access_token: &str,
     url: String,
-) -> TwitchStreamsResponse {
+) -> Result<TwitchStreamsResponse, Status> {
     let request = Request::builder()
         .uri(url)
         .method("GET")
         .header("Client-ID", twitch_client_id)
         .header("Authorization", format!("Bearer {}", access_token))
         .body(())
-        .map_err(|_| TwitchStreamsResponse {
-            data: vec![],
-            pagination: TwitchPagination { cursor: None },
-        })
-        .unwrap();
-
-    let body = Response::builder()
-        .body(AsyncBody::from(to_string(&TwitchStreamsResponse {
-            data: vec![],
-            pagination: TwitchPagination { cursor: None },
-        }).unwrap()));
-
-    let mut response = isahc::send_async(request)
-        .await
-        .map_err(|_| body.unwrap())
-        .unwrap();
-
-    response
-        .json()
-        .await
-        .unwrap_or_else(|_| TwitchStreamsResponse {
-            data: vec![],
-            pagination: TwitchPagination { cursor: None },
-        })
+        .map_err(|_| Status::InternalServerError)?;
+
+    let mut response = send("streams", Bucket::App, request).await.map_err(|e| {
+        warn!(error = %e, "streams request failed");
+        Status::ServiceUnavailable
+    })?;
+
+    if response.status() != StatusCode::OK {
+        warn!(status = %response.status(), "streams request rejected");
+        return Err(Status::BadGateway);
+    }
+
+    response.json().await.map_err(|e| {
+        warn!(error = %e, "unreadable streams response");
+        Status::BadGateway
+    })
 }
@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
 use isahc::{AsyncReadResponseExt, Request};
-use rocket::info;
+use tracing::{debug, info};
 use serde::{Deserialize, Serialize};
 
-use super::TwitchPagination;
+use super::{send, Bucket, TwitchPagination};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Localization {
@@ -36,7 +36,7 @@ pub async fn get_all_tags(
 
     let url = format!("https://api.twitch.tv/helix/tags/streams?first=100{}", after_query);
 
-    info!("requesting url {}", url);
+    debug!(%url, "requesting tags");
 
     let request = Request::builder()
         .uri(url)
@@ -46,11 +46,9 @@ pub async fn get_all_tags(
         .body(())
         .unwrap();
 
-    let mut response = isahc::send_async(request)
+    let mut response = send("tags/streams", Bucket::App, request)
         .await.unwrap();
 
-    println!("{:?}", response.text().await);
-
     response.json().await.unwrap()
 }
 
@@ -71,7 +69,7 @@ pub async fn get_all_tags_map(
         all_tags_map.keys().len()
     );
     while cursor.is_some() {
-        info!("fetch_all_tags: fetching cursor {:?}", cursor);
+        debug!(?cursor, "fetch_all_tags: fetching cursor");
 
         let tags_response = get_all_tags(client_id, access_token, cursor.unwrap().as_str()).await;
 
@@ -90,7 +88,7 @@ pub async fn get_all_tags_map(
         cursor = tags_response.pagination.cursor;
     }
 
-    info!("ALL TAGS {:#?}", all_tags_map);
+    info!(tags = all_tags_map.len(), "fetch_all_tags: finished");
 
     all_tags_map
 }
@@ -1,7 +1,7 @@
 use isahc::{AsyncReadResponseExt, Request};
 use serde::{Deserialize, Serialize};
 
-use crate::clients::twitch::TwitchPagination;
+use crate::clients::twitch::{send, Bucket, TwitchPagination};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TwitchUserFollow {
@@ -45,7 +45,7 @@ pub async fn get_user_follows(
         })
         .unwrap();
 
-    let mut response = isahc::send_async(request)
+    let mut response = send("users/follows", Bucket::Other, request)
         .await
         .map_err(|_| TwitchUserFollows {
             data: vec![],
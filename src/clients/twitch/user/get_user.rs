@@ -1,6 +1,9 @@
 use isahc::{http::StatusCode, AsyncReadResponseExt, Request};
-use rocket::{http::Status, info};
+use rocket::http::Status;
 use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::clients::twitch::{send, Bucket};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TwitchUserResponse {
@@ -36,10 +39,10 @@ pub async fn get_user(
         .body(())
         .unwrap();
 
-    let mut response = isahc::send_async(request).await.unwrap();
+    let mut response = send("users", Bucket::App, request).await.unwrap();
 
     if response.status() != StatusCode::OK {
-        info!("user not found failed with {:?}", response.status());
+        info!(status = %response.status(), username, "user not found");
         return Err(Status::NotFound);
     }
     Ok(response.json().await.unwrap())
@@ -1,6 +1,7 @@
-use crate::clients::twitch::streams::TwitchStreamsResponse;
+use crate::clients::twitch::{send, streams::TwitchStreamsResponse, Bucket};
 use isahc::{http::StatusCode, AsyncReadResponseExt, Request};
-use rocket::{http::Status, info};
+use rocket::http::Status;
+use tracing::info;
 
 pub async fn get_stream(
     twitch_client_id: &str,
@@ -18,10 +19,10 @@ pub async fn get_stream(
         .body(())
         .unwrap();
 
-    let mut response = isahc::send_async(request).await.unwrap();
+    let mut response = send("streams", Bucket::App, request).await.unwrap();
 
     if response.status() != StatusCode::OK {
-        info!("streams not found failed with {:?}", response.status());
+        info!(status = %response.status(), username, "stream not found");
         return Err(Status::NotFound);
     }
     Ok(response.json().await.unwrap())
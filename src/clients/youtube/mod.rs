@@ -0,0 +1,268 @@
+use std::time::Instant;
+
+use isahc::{http::StatusCode, AsyncBody, AsyncReadResponseExt, Request, Response};
+use rocket::http::{RawStr, Status};
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{debug, debug_span, info, Instrument};
+
+use crate::metrics::{YOUTUBE_REQUESTS, YOUTUBE_REQUEST_DURATION};
+
+/// Quota units each YouTube Data API call costs.
+pub const SEARCH_COST: u64 = 100;
+pub const LIST_COST: u64 = 1;
+
+/// Most ids `videos.list` accepts in one call.
+pub const MAX_VIDEO_IDS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeSearchResponse {
+    pub next_page_token: Option<String>,
+    #[serde(default)]
+    pub items: Vec<YoutubeSearchItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeSearchItem {
+    pub id: YoutubeSearchItemId,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeSearchItemId {
+    pub video_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeVideosResponse {
+    #[serde(default)]
+    pub items: Vec<YoutubeVideo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeVideo {
+    pub id: String,
+    pub snippet: YoutubeVideoSnippet,
+    pub live_streaming_details: Option<YoutubeLiveStreamingDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeVideoSnippet {
+    pub channel_id: String,
+    pub channel_title: String,
+    pub title: String,
+    #[serde(default)]
+    pub category_id: String,
+    /// `live`, `upcoming` or `none`.
+    pub live_broadcast_content: String,
+    pub default_audio_language: Option<String>,
+    pub default_language: Option<String>,
+    #[serde(default)]
+    pub thumbnails: YoutubeThumbnails,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct YoutubeThumbnails {
+    pub high: Option<YoutubeThumbnail>,
+    pub medium: Option<YoutubeThumbnail>,
+    pub default: Option<YoutubeThumbnail>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeThumbnail {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeLiveStreamingDetails {
+    pub actual_start_time: Option<String>,
+    pub actual_end_time: Option<String>,
+    /// Sent as a string, and left out when the channel hides its viewer count.
+    pub concurrent_viewers: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubePlaylistItemsResponse {
+    #[serde(default)]
+    pub items: Vec<YoutubePlaylistItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubePlaylistItem {
+    pub content_details: YoutubePlaylistItemDetails,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubePlaylistItemDetails {
+    pub video_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeChannelsResponse {
+    #[serde(default)]
+    pub items: Vec<YoutubeChannel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeChannel {
+    pub id: String,
+    pub snippet: YoutubeChannelSnippet,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeChannelSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub custom_url: Option<String>,
+    #[serde(default)]
+    pub thumbnails: YoutubeThumbnails,
+}
+
+fn encode(value: &str) -> String {
+    RawStr::new(value).percent_encode().to_string()
+}
+
+/// Sends a request to YouTube, recording its status and duration.
+async fn send(endpoint: &str, url: String) -> Result<Response<AsyncBody>, isahc::Error> {
+    let request = Request::get(url).body(()).map_err(isahc::Error::from)?;
+
+    let started_at = Instant::now();
+    let response = isahc::send_async(request)
+        .instrument(debug_span!("youtube_request", endpoint))
+        .await;
+
+    let status = match &response {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "error".to_owned(),
+    };
+    debug!(
+        endpoint,
+        status = %status,
+        duration_ms = started_at.elapsed().as_millis() as u64,
+        "youtube request finished"
+    );
+    YOUTUBE_REQUESTS
+        .with_label_values(&[endpoint, &status])
+        .inc();
+    YOUTUBE_REQUEST_DURATION
+        .with_label_values(&[endpoint, &status])
+        .observe(started_at.elapsed().as_secs_f64());
+
+    response
+}
+
+async fn get_json<T: DeserializeOwned + Unpin>(endpoint: &str, url: String) -> Result<T, Status> {
+    let mut response = send(endpoint, url)
+        .await
+        .map_err(|_| Status::ServiceUnavailable)?;
+
+    match response.status() {
+        StatusCode::OK => response.json().await.map_err(|_| Status::BadGateway),
+        // quota exhaustion is reported as a 403 with a `quotaExceeded` reason
+        StatusCode::FORBIDDEN => {
+            info!(
+                endpoint,
+                "youtube request forbidden, quota may be exhausted"
+            );
+            Err(Status::TooManyRequests)
+        }
+        StatusCode::NOT_FOUND => Err(Status::NotFound),
+        status => {
+            info!(endpoint, %status, "youtube request failed");
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+/// Searches live videos matching `query` and/or in `category_id`. Costs [`SEARCH_COST`].
+pub async fn search_live(
+    base_url: &str,
+    api_key: &str,
+    query: Option<&str>,
+    category_id: Option<&str>,
+    page_token: Option<&str>,
+) -> Result<YoutubeSearchResponse, Status> {
+    let mut url = format!(
+        "{}/search?part=id&eventType=live&type=video&maxResults=50&order=viewCount&key={}",
+        base_url,
+        encode(api_key)
+    );
+    if let Some(query) = query {
+        url.push_str(&format!("&q={}", encode(query)));
+    }
+    if let Some(category_id) = category_id {
+        url.push_str(&format!("&videoCategoryId={}", encode(category_id)));
+    }
+    if let Some(page_token) = page_token {
+        url.push_str(&format!("&pageToken={}", encode(page_token)));
+    }
+
+    get_json("search", url).await
+}
+
+/// Looks up at most [`MAX_VIDEO_IDS`] videos with their live details. Costs [`LIST_COST`].
+pub async fn get_videos(
+    base_url: &str,
+    api_key: &str,
+    ids: &[String],
+) -> Result<YoutubeVideosResponse, Status> {
+    let url = format!(
+        "{}/videos?part=snippet,liveStreamingDetails&id={}&key={}",
+        base_url,
+        encode(&ids.join(",")),
+        encode(api_key)
+    );
+
+    get_json("videos", url).await
+}
+
+/// Lists a channel's latest uploads, which include its live broadcasts. Costs [`LIST_COST`].
+pub async fn get_recent_uploads(
+    base_url: &str,
+    api_key: &str,
+    channel_id: &str,
+) -> Result<YoutubePlaylistItemsResponse, Status> {
+    // every channel's uploads playlist id is its channel id with `UC` swapped for `UU`
+    let playlist_id = match channel_id.strip_prefix("UC") {
+        Some(rest) => format!("UU{}", rest),
+        None => return Err(Status::NotFound),
+    };
+
+    let url = format!(
+        "{}/playlistItems?part=contentDetails&maxResults=5&playlistId={}&key={}",
+        base_url,
+        encode(&playlist_id),
+        encode(api_key)
+    );
+
+    get_json("playlistItems", url).await
+}
+
+/// Looks up a channel by id, or by `@handle` when `channel` starts with `@`. Costs [`LIST_COST`].
+pub async fn get_channel(
+    base_url: &str,
+    api_key: &str,
+    channel: &str,
+) -> Result<YoutubeChannelsResponse, Status> {
+    let filter = match channel.starts_with('@') {
+        true => "forHandle",
+        false => "id",
+    };
+
+    let url = format!(
+        "{}/channels?part=snippet&{}={}&key={}",
+        base_url,
+        filter,
+        encode(channel),
+        encode(api_key)
+    );
+
+    get_json("channels", url).await
+}
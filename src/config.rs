@@ -0,0 +1,323 @@
+use std::{fmt, fs, path::PathBuf};
+
+use rand::RngCore;
+use rocket::config::SecretKey;
+use rocket::figment::{
+    providers::Serialized,
+    value::{Dict, Value},
+    Figment, Profile,
+};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{creators::Creator, fairings::cors::CorsConfig, guards::api_key::ApiKeyConfig};
+
+/// Profiles the config may select with `ROCKET_PROFILE`.
+const PROFILES: &[&str] = &["debug", "release", "dev", "staging", "prod"];
+
+/// Shown in place of a secret's value.
+pub const REDACTED: &str = "[redacted]";
+
+/// A config value that's kept out of logs and the admin config; its `Debug` and `Serialize`
+/// output is redacted.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+fn default_login_scopes() -> Vec<String> {
+    vec!["user:read:follows".to_owned()]
+}
+
+fn default_login_redirect() -> String {
+    "/".to_owned()
+}
+
+fn default_log_format() -> String {
+    "text".to_owned()
+}
+
+fn default_compression_min_size() -> usize {
+    1024
+}
+
+fn default_max_snapshot_age() -> u64 {
+    120
+}
+
+fn default_settings_watch_interval() -> u64 {
+    5
+}
+
+/// The keys read once at startup. A missing key takes its default, but one that's set has to
+/// parse, so a typo fails [`load`] rather than quietly falling back.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ServiceConfig {
+    #[serde(default = "default_log_format")]
+    pub log_format: String,
+    pub log_filter: Option<String>,
+    #[serde(default)]
+    pub twitch_required_scopes: Vec<String>,
+    #[serde(default)]
+    pub twitch_allowed_client_ids: Vec<String>,
+    pub twitch_redirect_uri: Option<String>,
+    #[serde(default = "default_login_scopes")]
+    pub twitch_login_scopes: Vec<String>,
+    #[serde(default = "default_login_redirect")]
+    pub twitch_login_redirect: String,
+    pub admin_token: Option<Secret>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    #[serde(default)]
+    pub api_key_required: bool,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default = "default_compression_min_size")]
+    pub compression_min_size: usize,
+    #[serde(default = "default_max_snapshot_age")]
+    pub readiness_max_snapshot_age: u64,
+    pub snapshot_path: Option<PathBuf>,
+    #[serde(default)]
+    pub creators: Vec<Creator>,
+    pub denylist_path: Option<PathBuf>,
+    pub settings_path: Option<PathBuf>,
+    #[serde(default = "default_settings_watch_interval")]
+    pub settings_watch_interval: u64,
+}
+
+/// Reads the value of every `<key>_file` key from the file it names, at any depth, so that
+/// `ROCKET_TWITCH_CLIENT_SECRET_FILE=/run/secrets/twitch` sets `twitch_client_secret`.
+fn read_secret_files(dict: &Dict, path: &str) -> Result<Dict, String> {
+    let mut secrets = Dict::new();
+
+    for (key, value) in dict {
+        let full_key = match path {
+            "" => key.clone(),
+            _ => format!("{}.{}", path, key),
+        };
+
+        if let Value::Dict(_, nested) = value {
+            let nested = read_secret_files(nested, &full_key)?;
+            if !nested.is_empty() {
+                secrets.insert(key.clone(), nested.into());
+            }
+            continue;
+        }
+
+        let name = match key.strip_suffix("_file") {
+            Some(name) => name,
+            None => continue,
+        };
+        if dict.contains_key(name) {
+            return Err(format!(
+                "both {0} and {0}_file are set, only one may be",
+                full_key.trim_end_matches("_file")
+            ));
+        }
+
+        let file = value
+            .as_str()
+            .ok_or_else(|| format!("{} must be a path", full_key))?;
+        let secret = fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {} from {}: {}", full_key, file, e))?;
+        secrets.insert(
+            name.to_owned(),
+            secret.trim_end_matches(&['\r', '\n'][..]).into(),
+        );
+    }
+
+    Ok(secrets)
+}
+
+fn is_set(figment: &Figment, key: &str) -> bool {
+    figment
+        .extract_inner::<String>(key)
+        .is_ok_and(|value| !value.is_empty())
+}
+
+/// Builds the config the way Rocket does, so the `[default]` table of `Rocket.toml` is
+/// overridden by the table of the profile `ROCKET_PROFILE` selects (dev, staging or prod), and
+/// both by `ROCKET_*` env vars. Any key can also be read from a file with a `_file` suffix.
+///
+/// Fails with every problem found, rather than the first one a route would trip over.
+pub fn load() -> Result<(Figment, ServiceConfig), String> {
+    let figment = rocket::Config::figment();
+
+    let data: Dict = figment.extract().map_err(|e| e.to_string())?;
+    let secrets = read_secret_files(&data, "")?;
+    let mut figment = figment.merge(Serialized::globals(secrets));
+
+    let profile = figment.profile().clone();
+    let mut errors = vec![];
+
+    if !PROFILES.iter().any(|known| profile == Profile::new(known)) {
+        errors.push(format!(
+            "unknown profile {}, ROCKET_PROFILE must be one of {}",
+            profile,
+            PROFILES.join(", ")
+        ));
+    }
+
+    for key in ["twitch_client_id", "twitch_client_secret"] {
+        if !is_set(&figment, key) {
+            errors.push(format!(
+                "{0} is required: set ROCKET_{1} or ROCKET_{1}_FILE",
+                key,
+                key.to_uppercase()
+            ));
+        }
+    }
+
+    let service = match figment.extract::<ServiceConfig>() {
+        Ok(service) => Some(service),
+        Err(e) => {
+            errors.extend(e.into_iter().map(|e| e.to_string()));
+            None
+        }
+    };
+
+    if let Some(service) = &service {
+        if let Err(e) = service.cors.validate() {
+            errors.push(e);
+        }
+        if service
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.expose().is_empty())
+        {
+            errors.push("admin_token is empty, unset it to disable the admin routes".to_owned());
+        }
+    }
+
+    if !figment
+        .extract_inner::<SecretKey>("secret_key")
+        .is_ok_and(|key| key.is_provided())
+    {
+        if profile == Profile::new("dev") {
+            // like rocket's debug profile, dev gets a key that only lasts until a restart
+            let mut key = [0u8; 64];
+            rand::thread_rng().fill_bytes(&mut key);
+            figment = figment.merge(("secret_key", base64::encode(key)));
+        } else if profile != rocket::Config::DEBUG_PROFILE {
+            errors.push(format!(
+                "the {} profile needs secret_key: set ROCKET_SECRET_KEY or ROCKET_SECRET_KEY_FILE",
+                profile
+            ));
+        }
+    }
+
+    match service {
+        Some(service) if errors.is_empty() => Ok((figment, service)),
+        _ => Err(errors.join("; ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// Writes `contents` to a file of its own, named after the test.
+    fn secret_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("{}-{}", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn dict(entries: &[(&str, Value)]) -> Dict {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_file_keys_at_any_depth() {
+        let secret = secret_file("config-test-secret", "s3cret\n");
+        let api_key = secret_file("config-test-api-key", "y0utube\r\n");
+        let data = dict(&[
+            ("twitch_client_secret_file", secret.as_str().into()),
+            ("twitch_client_id", "id".into()),
+            (
+                "youtube",
+                dict(&[("api_key_file", api_key.as_str().into())]).into(),
+            ),
+            (
+                "kick",
+                dict(&[("base_url", "https://kick.com".into())]).into(),
+            ),
+        ]);
+
+        let secrets = read_secret_files(&data, "").unwrap();
+
+        // trailing newlines are trimmed, and dicts without file keys left out
+        let expected = dict(&[
+            ("twitch_client_secret", "s3cret".into()),
+            ("youtube", dict(&[("api_key", "y0utube".into())]).into()),
+        ]);
+        assert_eq!(secrets, expected);
+    }
+
+    #[test]
+    fn rejects_a_key_set_both_ways() {
+        let secret = secret_file("config-test-both", "s3cret");
+        let data = dict(&[(
+            "youtube",
+            dict(&[
+                ("api_key", "inline".into()),
+                ("api_key_file", secret.as_str().into()),
+            ])
+            .into(),
+        )]);
+
+        let error = read_secret_files(&data, "").unwrap_err();
+        assert!(
+            error.contains("youtube.api_key and youtube.api_key_file"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_file() {
+        let data = dict(&[("admin_token_file", "/nonexistent/admin_token".into())]);
+
+        let error = read_secret_files(&data, "").unwrap_err();
+        assert!(
+            error.contains("failed to read admin_token_file"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_a_file_key_that_isnt_a_path() {
+        let data = dict(&[("admin_token_file", 42.into())]);
+
+        let error = read_secret_files(&data, "").unwrap_err();
+        assert_eq!(error, "admin_token_file must be a path");
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::providers::Platform;
+
+/// One of a creator's channels.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CreatorAccount {
+    pub platform: Platform,
+    /// The login streams on the platform carry: the Twitch login, the Kick slug or the
+    /// YouTube channel id.
+    pub login: String,
+}
+
+impl CreatorAccount {
+    pub fn url(&self) -> String {
+        self.platform.channel_url(&self.login)
+    }
+}
+
+/// Marks a `/stream` lookup by creator id rather than login.
+const CREATOR_ID_PREFIX: &str = "creator:";
+
+/// A developer and their channels across platforms, read from the `creators` config key.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Creator {
+    pub id: String,
+    pub name: String,
+    pub accounts: Vec<CreatorAccount>,
+}
+
+/// Maps creator ids and platform accounts to creators.
+#[derive(Debug, Default, Clone)]
+pub struct CreatorRegistry {
+    creators: Vec<Creator>,
+    by_id: HashMap<String, usize>,
+    /// Keyed by the lowercased login.
+    by_account: HashMap<(Platform, String), usize>,
+}
+
+impl CreatorRegistry {
+    pub fn new(creators: Vec<Creator>) -> Self {
+        let mut by_id = HashMap::new();
+        let mut by_account = HashMap::new();
+
+        for (i, creator) in creators.iter().enumerate() {
+            by_id.insert(creator.id.to_lowercase(), i);
+            for account in &creator.accounts {
+                by_account.insert((account.platform, account.login.to_lowercase()), i);
+            }
+        }
+
+        Self {
+            creators,
+            by_id,
+            by_account,
+        }
+    }
+
+    /// Identifies the registered creators and their accounts, which listings are grouped by.
+    pub fn fingerprint(&self) -> String {
+        let mut accounts: Vec<String> = self
+            .by_account
+            .iter()
+            .map(|((platform, login), &i)| {
+                format!("{}:{}={}", platform, login, self.creators[i].id)
+            })
+            .collect();
+        accounts.sort();
+        accounts.join(",")
+    }
+
+    pub fn for_account(&self, platform: Platform, login: &str) -> Option<&Creator> {
+        self.by_account
+            .get(&(platform, login.to_lowercase()))
+            .map(|&i| &self.creators[i])
+    }
+
+    /// Resolves a creator from one of their logins on `platform`, or from their id when `name`
+    /// is `creator:<id>`. The prefix keeps ids from shadowing a channel of the same name, and no
+    /// platform allows a `:` in logins.
+    pub fn resolve(&self, platform: Platform, name: &str) -> Option<&Creator> {
+        match name.strip_prefix(CREATOR_ID_PREFIX) {
+            Some(id) => self
+                .by_id
+                .get(&id.to_lowercase())
+                .map(|&i| &self.creators[i]),
+            None => self.for_account(platform, name),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, fs, io, path::PathBuf, sync::RwLock};
+
+use rocket::serde::json::serde_json;
+use serde::{Deserialize, Serialize};
+
+use crate::{metrics::unix_now, providers::Platform};
+
+/// A broadcaster hidden from the listings.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DenylistEntry {
+    #[serde(default)]
+    pub platform: Platform,
+    pub broadcaster_id: String,
+    pub reason: String,
+    /// Who added the entry.
+    pub actor: String,
+    /// Unix time the entry was added at.
+    pub added_at: i64,
+    /// Unix time the entry stops applying at, never when unset.
+    pub expires_at: Option<i64>,
+}
+
+impl DenylistEntry {
+    fn is_active(&self, now: i64) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+type DenylistKey = (Platform, String);
+
+/// Broadcasters hidden from the listings, saved to `denylist_path` on every change.
+pub struct Denylist {
+    entries: RwLock<HashMap<DenylistKey, DenylistEntry>>,
+    path: Option<PathBuf>,
+}
+
+impl Denylist {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            path,
+        }
+    }
+
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let entries: Vec<DenylistEntry> = serde_json::from_slice(&fs::read(&path)?)?;
+        let entries = entries
+            .into_iter()
+            .map(|entry| ((entry.platform, entry.broadcaster_id.clone()), entry))
+            .collect();
+
+        Ok(Self {
+            entries: RwLock::new(entries),
+            path: Some(path),
+        })
+    }
+
+    pub fn is_denied(&self, platform: Platform, broadcaster_id: &str) -> bool {
+        self.entries
+            .read()
+            .unwrap()
+            .get(&(platform, broadcaster_id.to_owned()))
+            .is_some_and(|entry| entry.is_active(unix_now()))
+    }
+
+    /// Entries that still apply, oldest first.
+    pub fn entries(&self) -> Vec<DenylistEntry> {
+        let now = unix_now();
+        let mut entries: Vec<DenylistEntry> = self
+            .entries
+            .read()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.is_active(now))
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.added_at);
+        entries
+    }
+
+    /// Identifies the broadcasters denied right now; listings built under a different set
+    /// need a different `ETag`.
+    pub fn fingerprint(&self) -> String {
+        let mut keys: Vec<String> = self
+            .entries()
+            .into_iter()
+            .map(|entry| format!("{}:{}", entry.platform, entry.broadcaster_id))
+            .collect();
+        keys.sort();
+        keys.join(",")
+    }
+
+    /// Adds or replaces the entry for a broadcaster.
+    pub fn add(&self, entry: DenylistEntry) -> io::Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        entries.insert((entry.platform, entry.broadcaster_id.clone()), entry);
+        self.save(&mut entries)
+    }
+
+    pub fn remove(
+        &self,
+        platform: Platform,
+        broadcaster_id: &str,
+    ) -> io::Result<Option<DenylistEntry>> {
+        let mut entries = self.entries.write().unwrap();
+        let removed = entries.remove(&(platform, broadcaster_id.to_owned()));
+        self.save(&mut entries)?;
+        Ok(removed)
+    }
+
+    /// Drops expired entries and writes the rest out, when a path is configured.
+    fn save(&self, entries: &mut HashMap<DenylistKey, DenylistEntry>) -> io::Result<()> {
+        let now = unix_now();
+        entries.retain(|_, entry| entry.is_active(now));
+
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let persisted: Vec<&DenylistEntry> = entries.values().collect();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&persisted)?)?;
+        fs::rename(&tmp, path)
+    }
+}
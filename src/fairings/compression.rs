@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::Lazy;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Status,
+    Request, Response,
+};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+// order of preference when a client accepts several encodings equally
+const ENCODINGS: &[Encoding] = &[Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    pub fn compress(&self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                writer.write_all(body)?;
+                Ok(writer.into_inner())
+            }
+            Encoding::Zstd => zstd::encode_all(body, 3),
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Picks the preferred encoding the client accepts from `Accept-Encoding`.
+    pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+        let accepted: Vec<(&str, f32)> = accept_encoding
+            .split(',')
+            .filter_map(|part| {
+                let mut params = part.split(';');
+                let name = params.next()?.trim();
+                let quality = params
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((name, quality))
+            })
+            .collect();
+
+        let quality = |encoding: &Encoding| {
+            accepted
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(encoding.name()))
+                .or_else(|| accepted.iter().find(|(name, _)| *name == "*"))
+                .map_or(0.0, |(_, q)| *q)
+        };
+
+        ENCODINGS
+            .iter()
+            .map(|encoding| (*encoding, quality(encoding)))
+            .filter(|(_, q)| *q > 0.0)
+            .fold(
+                None,
+                |best: Option<(Encoding, f32)>, (encoding, q)| match best {
+                    Some((_, best_q)) if best_q >= q => best,
+                    _ => Some((encoding, q)),
+                },
+            )
+            .map(|(encoding, _)| encoding)
+    }
+
+    /// Removes the suffix added to the `ETag` of a compressed representation.
+    pub fn strip_etag_suffix(etag: &str) -> String {
+        let unquoted = etag.trim_matches('"');
+
+        ENCODINGS
+            .iter()
+            .find_map(|encoding| unquoted.strip_suffix(&format!("-{}", encoding.name())))
+            .map_or_else(|| etag.to_owned(), |tag| format!("\"{}\"", tag))
+    }
+
+    fn etag(&self, etag: &str) -> String {
+        format!("\"{}-{}\"", etag.trim_matches('"'), self.name())
+    }
+}
+
+struct SharedBody(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBody {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+type CompressedBodies = HashMap<(String, Encoding), Arc<Vec<u8>>>;
+
+/// Compressed bodies of the current snapshot's listings, keyed by `ETag`.
+pub struct CompressionCache {
+    entries: Mutex<CompressedBodies>,
+}
+
+pub static COMPRESSION_CACHE: Lazy<CompressionCache> = Lazy::new(|| CompressionCache {
+    entries: Mutex::new(HashMap::new()),
+});
+
+impl CompressionCache {
+    fn get(&self, etag: &str, encoding: Encoding) -> Option<Arc<Vec<u8>>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(etag.trim_matches('"').to_owned(), encoding))
+            .cloned()
+    }
+
+    fn insert(&self, etag: &str, encoding: Encoding, body: Arc<Vec<u8>>) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((etag.trim_matches('"').to_owned(), encoding), body);
+    }
+
+    /// Drops the previous snapshot's bodies and compresses `body` in every encoding.
+    pub fn replace(&self, etag: &str, body: &[u8]) {
+        let compressed: Vec<(Encoding, Arc<Vec<u8>>)> = ENCODINGS
+            .iter()
+            .filter_map(|encoding| Some((*encoding, Arc::new(encoding.compress(body).ok()?))))
+            .collect();
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.clear();
+        for (encoding, body) in compressed {
+            entries.insert((etag.trim_matches('"').to_owned(), encoding), body);
+        }
+    }
+}
+
+pub struct Compression {
+    /// Bodies smaller than this many bytes are sent uncompressed.
+    min_size: usize,
+}
+
+impl Compression {
+    pub fn new(min_size: usize) -> Self {
+        Self { min_size }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let is_candidate = res.status() == Status::Ok || res.status() == Status::NotModified;
+        if !is_candidate || res.headers().contains("Content-Encoding") {
+            return;
+        }
+
+        // caches must key on Accept-Encoding whether or not this response ends up compressed,
+        // or they'd serve a plain body to gzip clients and a gzip body to everyone else
+        res.adjoin_raw_header("Vary", "Accept-Encoding");
+
+        let encoding = match req
+            .headers()
+            .get_one("Accept-Encoding")
+            .and_then(Encoding::negotiate)
+        {
+            Some(encoding) => encoding,
+            None => return,
+        };
+
+        let etag = res.headers().get_one("ETag").map(|e| e.to_owned());
+
+        if res.status() == Status::NotModified {
+            // echo the tag of the compressed representation the client revalidated
+            if let Some(etag) = etag {
+                let matched = req
+                    .headers()
+                    .get("If-None-Match")
+                    .flat_map(|header| header.split(','))
+                    .any(|tag| tag.trim() == encoding.etag(&etag));
+                if matched {
+                    res.set_raw_header("ETag", encoding.etag(&etag));
+                }
+            }
+            return;
+        }
+
+        if let Some(size) = res.body().preset_size() {
+            if size < self.min_size {
+                return;
+            }
+        }
+
+        let cached = etag
+            .as_deref()
+            .and_then(|etag| COMPRESSION_CACHE.get(etag, encoding));
+
+        let compressed = match cached {
+            Some(compressed) => compressed,
+            None => {
+                let body = match res.body_mut().to_bytes().await {
+                    Ok(body) => body,
+                    Err(_) => return,
+                };
+
+                if body.len() < self.min_size {
+                    res.set_sized_body(body.len(), Cursor::new(body));
+                    return;
+                }
+
+                match encoding.compress(&body) {
+                    Ok(compressed) => {
+                        let compressed = Arc::new(compressed);
+                        if let Some(etag) = &etag {
+                            COMPRESSION_CACHE.insert(etag, encoding, compressed.clone());
+                        }
+                        compressed
+                    }
+                    Err(e) => {
+                        warn!(?encoding, error = %e, "failed to compress response");
+                        res.set_sized_body(body.len(), Cursor::new(body));
+                        return;
+                    }
+                }
+            }
+        };
+
+        res.set_sized_body(compressed.len(), Cursor::new(SharedBody(compressed)));
+        res.set_raw_header("Content-Encoding", encoding.name());
+
+        if let Some(etag) = etag {
+            res.set_raw_header("ETag", encoding.etag(&etag));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_prefers_brotli_among_equals() {
+        assert_eq!(
+            Encoding::negotiate("gzip, deflate, br, zstd"),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(Encoding::negotiate("gzip, zstd"), Some(Encoding::Zstd));
+        assert_eq!(Encoding::negotiate("GZIP"), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn negotiate_follows_quality_values() {
+        assert_eq!(
+            Encoding::negotiate("br;q=0.5, gzip;q=0.8"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(Encoding::negotiate("*;q=0.1, br;q=0"), Some(Encoding::Zstd));
+        assert_eq!(Encoding::negotiate("gzip;q=0"), None);
+    }
+
+    #[test]
+    fn negotiate_ignores_unsupported_encodings() {
+        assert_eq!(Encoding::negotiate("deflate, identity"), None);
+        assert_eq!(Encoding::negotiate(""), None);
+    }
+
+    #[test]
+    fn etag_suffix_round_trips() {
+        let etag = "\"abc-all\"";
+
+        for encoding in ENCODINGS {
+            let compressed = encoding.etag(etag);
+            assert_eq!(compressed, format!("\"abc-all-{}\"", encoding.name()));
+            assert_eq!(Encoding::strip_etag_suffix(&compressed), etag);
+        }
+    }
+
+    #[test]
+    fn strip_etag_suffix_leaves_other_tags_alone() {
+        assert_eq!(Encoding::strip_etag_suffix("\"abc-all\""), "\"abc-all\"");
+        assert_eq!(
+            Encoding::strip_etag_suffix("\"abc-gzipped\""),
+            "\"abc-gzipped\""
+        );
+        // only a suffix counts, not an encoding name inside the tag
+        assert_eq!(Encoding::strip_etag_suffix("\"br-abc\""), "\"br-abc\"");
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::Cursor;
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{Method, Status},
+    Request, Response,
+};
+use serde::{Deserialize, Serialize};
+
+fn default_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "OPTIONS"]
+        .iter()
+        .map(|m| m.to_string())
+        .collect()
+}
+
+fn default_allowed_headers() -> Vec<String> {
+    ["Authorization", "token", "Content-Type", "X-Api-Key"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect()
+}
+
+fn default_max_age() -> u64 {
+    86_400
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CorsConfig {
+    /// Origins allowed to call the api, `*` allows every origin.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Lets browsers send the login session cookies cross-origin.
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// Seconds browsers may cache a preflight response.
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: default_allowed_methods(),
+            allowed_headers: default_allowed_headers(),
+            allow_credentials: false,
+            max_age: default_max_age(),
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Browsers reject a `*` origin on credentialed responses, and echoing whatever origin
+    /// asks would hand the session cookies to every site.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allow_credentials && self.allowed_origins.iter().any(|o| o == "*") {
+            return Err(
+                "cors.allow_credentials can't be combined with a * origin, list the origins instead"
+                    .to_owned(),
+            );
+        }
+        Ok(())
+    }
+}
+
+pub struct Cors {
+    config: CorsConfig,
+}
+
+impl Cors {
+    pub fn new(config: CorsConfig) -> Self {
+        Self { config }
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.config.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    fn is_allowed_origin(&self, origin: &str) -> bool {
+        self.allows_any_origin()
+            || self
+                .config
+                .allowed_origins
+                .iter()
+                .any(|o| o.eq_ignore_ascii_case(origin))
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let origin = match req.headers().get_one("Origin") {
+            Some(origin) if self.is_allowed_origin(origin) => origin,
+            _ => return,
+        };
+
+        // credentialed requests need the exact origin echoed back, and config loading rejects
+        // a * origin with credentials
+        let allow_origin = match self.allows_any_origin() {
+            true => "*",
+            false => origin,
+        };
+
+        res.set_raw_header("Access-Control-Allow-Origin", allow_origin.to_owned());
+        res.adjoin_raw_header("Vary", "Origin");
+        res.set_raw_header(
+            "Access-Control-Expose-Headers",
+            "WWW-Authenticate, Retry-After, X-Request-Id",
+        );
+
+        if self.config.allow_credentials {
+            res.set_raw_header("Access-Control-Allow-Credentials", "true");
+        }
+
+        let is_preflight = req.method() == Method::Options
+            && req.headers().contains("Access-Control-Request-Method");
+
+        if is_preflight {
+            res.set_status(Status::NoContent);
+            res.set_sized_body(0, Cursor::new(""));
+            res.set_raw_header(
+                "Access-Control-Allow-Methods",
+                self.config.allowed_methods.join(", "),
+            );
+            res.set_raw_header(
+                "Access-Control-Allow-Headers",
+                self.config.allowed_headers.join(", "),
+            );
+            res.set_raw_header("Access-Control-Max-Age", self.config.max_age.to_string());
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Instant;
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Data, Request, Response,
+};
+
+use crate::metrics::{HTTP_REQUESTS, HTTP_REQUEST_DURATION};
+
+struct RequestStart(Option<Instant>);
+
+/// Records the count and latency of every request by route and status.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Some(Instant::now())));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let route = req
+            .route()
+            .and_then(|route| route.name.as_deref())
+            .unwrap_or("unmatched");
+        let status = res.status().code.to_string();
+
+        HTTP_REQUESTS.with_label_values(&[route, &status]).inc();
+
+        if let Some(started_at) = req.local_cache(|| RequestStart(None)).0 {
+            HTTP_REQUEST_DURATION
+                .with_label_values(&[route, &status])
+                .observe(started_at.elapsed().as_secs_f64());
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod compression;
+pub mod cors;
+pub mod metrics;
+pub mod request_id;
+pub mod shutdown;
@@ -0,0 +1,109 @@
+use std::time::Instant;
+
+use rand::Rng;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    route::{self, Handler},
+    Data, Request, Response, Route,
+};
+use tracing::{info, info_span, Instrument};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Id of the current request, taken from `X-Request-Id` or generated.
+struct RequestTrace {
+    id: String,
+    started_at: Option<Instant>,
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn generate_request_id() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn new_trace(req: &Request<'_>, started_at: Option<Instant>) -> RequestTrace {
+    RequestTrace {
+        id: req
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|id| is_valid_request_id(id))
+            .map(|id| id.to_owned())
+            .unwrap_or_else(generate_request_id),
+        started_at,
+    }
+}
+
+fn request_trace<'r>(req: &'r Request<'_>) -> &'r RequestTrace {
+    req.local_cache(|| new_trace(req, None))
+}
+
+/// Tags every request with an id, echoes it back and logs the finished request.
+pub struct RequestIds;
+
+#[rocket::async_trait]
+impl Fairing for RequestIds {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ids",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let trace = new_trace(req, Some(Instant::now()));
+        req.local_cache(|| trace);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let trace = request_trace(req);
+        res.set_raw_header(REQUEST_ID_HEADER, trace.id.clone());
+
+        let route = req
+            .route()
+            .and_then(|route| route.name.as_deref())
+            .unwrap_or("unmatched");
+
+        info!(
+            request_id = %trace.id,
+            method = %req.method(),
+            // the query can hold secrets, such as the OAuth code and state on the login callback
+            path = %req.uri().path(),
+            route,
+            status = res.status().code,
+            duration_ms = trace.started_at.map(|t| t.elapsed().as_millis() as u64),
+            "request finished"
+        );
+    }
+}
+
+/// Runs a route's handler, guards included, in a span carrying the request id.
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let span = info_span!("request", request_id = %request_trace(req).id);
+        self.0.handle(req, data).instrument(span).await
+    }
+}
+
+/// Wraps the routes' handlers so that everything they log carries the request id; fairings
+/// can't reach into the handler's future to do it.
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
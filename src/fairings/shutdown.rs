@@ -0,0 +1,57 @@
+use std::{path::PathBuf, sync::Arc};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Orbit, Rocket,
+};
+use tracing::{info, warn};
+
+use crate::{poller::Poller, snapshot::SnapshotStore};
+
+/// Stops the stream poller on shutdown and saves the snapshot when persistence is enabled.
+pub struct GracefulShutdown {
+    poller: Arc<Poller>,
+    store: SnapshotStore,
+    snapshot_path: Option<PathBuf>,
+}
+
+impl GracefulShutdown {
+    pub fn new(poller: Arc<Poller>, store: SnapshotStore, snapshot_path: Option<PathBuf>) -> Self {
+        Self {
+            poller,
+            store,
+            snapshot_path,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for GracefulShutdown {
+    fn info(&self) -> Info {
+        Info {
+            name: "Graceful shutdown",
+            kind: Kind::Shutdown,
+        }
+    }
+
+    async fn on_shutdown(&self, _rocket: &Rocket<Orbit>) {
+        self.poller.stop().await;
+
+        let path = match &self.snapshot_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let snapshot = self.store.load();
+        if !snapshot.is_polled() {
+            return;
+        }
+
+        match snapshot.save(path) {
+            Ok(()) => {
+                info!(path = %path.display(), version = snapshot.version, "saved streams snapshot")
+            }
+            Err(e) => warn!(path = %path.display(), error = %e, "failed to save streams snapshot"),
+        }
+    }
+}
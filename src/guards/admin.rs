@@ -0,0 +1,37 @@
+use rocket::request::{self, FromRequest, Request};
+use rocket::{http::Status, outcome::Outcome};
+
+use crate::states::GlobalConfig;
+
+/// Request guard for the admin routes, which need the configured `admin_token`
+/// in an `X-Admin-Token` header.
+pub struct Admin;
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let admin_token = req
+            .rocket()
+            .state::<GlobalConfig>()
+            .and_then(|config| config.admin_token.as_ref());
+
+        // the admin routes don't exist unless a token is configured
+        let admin_token = match admin_token {
+            Some(admin_token) => admin_token,
+            None => return Outcome::Failure((Status::NotFound, ())),
+        };
+
+        match req.headers().get_one("X-Admin-Token") {
+            Some(token) if constant_time_eq(token.as_bytes(), admin_token.expose().as_bytes()) => {
+                Outcome::Success(Admin)
+            }
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use rocket::request::{self, FromRequest, Request};
+use rocket::{http::Status, outcome::Outcome};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Secret;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiKeyConfig {
+    pub name: String,
+    pub key: Secret,
+    /// Route names this key may call, every route when empty.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    pub requests_per_minute: u32,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+pub struct ApiKeyEntry {
+    pub config: ApiKeyConfig,
+    bucket: Mutex<TokenBucket>,
+    allowed: AtomicU64,
+    throttled: AtomicU64,
+}
+
+impl ApiKeyEntry {
+    fn new(config: ApiKeyConfig) -> Self {
+        let capacity = config.requests_per_minute as f64;
+        Self {
+            config,
+            bucket: Mutex::new(TokenBucket {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            }),
+            allowed: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    fn refill_rate(&self) -> f64 {
+        self.config.requests_per_minute as f64 / 60.0
+    }
+
+    fn refill(&self, bucket: &mut TokenBucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_rate())
+            .min(self.config.requests_per_minute as f64);
+        bucket.refilled_at = now;
+    }
+
+    /// Takes a token from the bucket, or returns how many seconds until one is available.
+    fn acquire(&self) -> Result<(), u64> {
+        let mut bucket = self.bucket.lock().unwrap();
+        self.refill(&mut bucket);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            self.allowed.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+        match self.refill_rate() > 0.0 {
+            true => Err(((1.0 - bucket.tokens) / self.refill_rate()).ceil() as u64),
+            false => Err(60),
+        }
+    }
+
+    fn allows_route(&self, route: Option<&str>) -> bool {
+        self.config.routes.is_empty()
+            || route.is_some_and(|route| self.config.routes.iter().any(|r| r == route))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyUsage {
+    pub name: String,
+    pub routes: Vec<String>,
+    pub requests_per_minute: u32,
+    pub remaining: u32,
+    pub allowed: u64,
+    pub throttled: u64,
+}
+
+pub struct ApiKeys {
+    /// Rejects requests without a key instead of serving them anonymously.
+    pub required: bool,
+    keys: HashMap<String, ApiKeyEntry>,
+}
+
+impl ApiKeys {
+    pub fn new(configs: Vec<ApiKeyConfig>, required: bool) -> Self {
+        let keys = configs
+            .into_iter()
+            .map(|config| (config.key.expose().to_owned(), ApiKeyEntry::new(config)))
+            .collect();
+
+        Self { required, keys }
+    }
+
+    pub fn usage(&self) -> Vec<ApiKeyUsage> {
+        let mut usage: Vec<ApiKeyUsage> = self
+            .keys
+            .values()
+            .map(|entry| {
+                let mut bucket = entry.bucket.lock().unwrap();
+                entry.refill(&mut bucket);
+
+                ApiKeyUsage {
+                    name: entry.config.name.clone(),
+                    routes: entry.config.routes.clone(),
+                    requests_per_minute: entry.config.requests_per_minute,
+                    remaining: bucket.tokens.floor() as u32,
+                    allowed: entry.allowed.load(Ordering::Relaxed),
+                    throttled: entry.throttled.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+
+        usage.sort_by(|a, b| a.name.cmp(&b.name));
+        usage
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiKeyError {
+    Missing,
+    Invalid,
+    RouteNotAllowed,
+    RateLimited { retry_after: u64 },
+}
+
+impl ApiKeyError {
+    pub fn status(&self) -> Status {
+        match self {
+            ApiKeyError::Missing | ApiKeyError::Invalid => Status::Unauthorized,
+            ApiKeyError::RouteNotAllowed => Status::Forbidden,
+            ApiKeyError::RateLimited { .. } => Status::TooManyRequests,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiKeyError::Missing => "api_key_missing",
+            ApiKeyError::Invalid => "api_key_invalid",
+            ApiKeyError::RouteNotAllowed => "api_key_route_not_allowed",
+            ApiKeyError::RateLimited { .. } => "rate_limited",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ApiKeyError::Missing => "an `X-Api-Key` header is required",
+            ApiKeyError::Invalid => "the api key is not recognised",
+            ApiKeyError::RouteNotAllowed => "the api key may not call this route",
+            ApiKeyError::RateLimited { .. } => "the api key has used up its quota",
+        }
+    }
+}
+
+/// Remembers why the guard failed so catchers can report it.
+pub struct ApiKeyFailure(pub Option<ApiKeyError>);
+
+/// Name of the api key a request was made with, `None` for anonymous requests.
+pub struct ApiKey(Option<String>);
+
+impl ApiKey {
+    /// The key's name for logs.
+    pub fn name(&self) -> &str {
+        self.0.as_deref().unwrap_or("anonymous")
+    }
+}
+
+fn check_api_key(req: &Request<'_>) -> Result<ApiKey, ApiKeyError> {
+    let api_keys = match req.rocket().state::<ApiKeys>() {
+        Some(api_keys) => api_keys,
+        None => return Ok(ApiKey(None)),
+    };
+
+    let key = match req.headers().get_one("X-Api-Key") {
+        Some(key) => key,
+        None if api_keys.required => return Err(ApiKeyError::Missing),
+        None => return Ok(ApiKey(None)),
+    };
+
+    let entry = api_keys.keys.get(key).ok_or(ApiKeyError::Invalid)?;
+
+    if !entry.allows_route(req.route().and_then(|r| r.name.as_deref())) {
+        return Err(ApiKeyError::RouteNotAllowed);
+    }
+
+    entry
+        .acquire()
+        .map_err(|retry_after| ApiKeyError::RateLimited { retry_after })?;
+
+    Ok(ApiKey(Some(entry.config.name.clone())))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    type Error = ApiKeyError;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match check_api_key(req) {
+            Ok(api_key) => Outcome::Success(api_key),
+            Err(e) => {
+                req.local_cache(|| ApiKeyFailure(Some(e)));
+                Outcome::Failure((e.status(), e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn entry(requests_per_minute: u32) -> ApiKeyEntry {
+        ApiKeyEntry::new(ApiKeyConfig {
+            name: "web".to_owned(),
+            key: "key".to_owned().into(),
+            routes: vec![],
+            requests_per_minute,
+        })
+    }
+
+    #[test]
+    fn a_full_bucket_allows_a_minute_of_requests() {
+        let entry = entry(60);
+
+        for _ in 0..60 {
+            assert_eq!(entry.acquire(), Ok(()));
+        }
+        assert!(entry.acquire().is_err());
+        assert_eq!(entry.allowed.load(Ordering::Relaxed), 60);
+        assert_eq!(entry.throttled.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn retry_after_is_the_wait_for_the_next_token() {
+        let entry = entry(6);
+        entry.bucket.lock().unwrap().tokens = 0.0;
+
+        // six a minute is a token every ten seconds
+        assert_eq!(entry.acquire(), Err(10));
+
+        entry.bucket.lock().unwrap().tokens = 0.5;
+        assert_eq!(entry.acquire(), Err(5));
+    }
+
+    #[test]
+    fn a_key_without_a_quota_waits_a_minute() {
+        let entry = entry(0);
+
+        assert_eq!(entry.acquire(), Err(60));
+    }
+
+    #[test]
+    fn the_bucket_refills_over_time_up_to_its_capacity() {
+        let entry = entry(60);
+        {
+            let mut bucket = entry.bucket.lock().unwrap();
+            bucket.tokens = 0.0;
+            bucket.refilled_at = Instant::now() - Duration::from_secs(30);
+        }
+        assert_eq!(entry.acquire(), Ok(()));
+        assert!(entry.bucket.lock().unwrap().tokens >= 28.9);
+
+        entry.bucket.lock().unwrap().refilled_at = Instant::now() - Duration::from_secs(600);
+        assert_eq!(entry.acquire(), Ok(()));
+        assert_eq!(entry.bucket.lock().unwrap().tokens, 59.0);
+    }
+}
@@ -1 +1,3 @@
+pub mod admin;
+pub mod api_key;
 pub mod twitch_auth;
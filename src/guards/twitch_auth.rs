@@ -1,7 +1,15 @@
-use isahc::AsyncReadResponseExt;
+use isahc::{http::StatusCode, AsyncReadResponseExt};
 use rocket::request::{self, FromRequest, Request};
 use rocket::{http::Status, outcome::Outcome};
 use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    clients::twitch::{send, Bucket},
+    metrics::USER_TOKENS_REJECTED,
+    session::session_access_token,
+    states::GlobalConfig,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct TwitchValidateToken {
@@ -17,52 +25,160 @@ pub struct AccessTokenResponse {
     pub token: String,
 }
 
-pub type TokenResponse = Outcome<AccessTokenResponse, (Status, AccessTokenError), ()>;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessTokenError {
+    Missing,
+    Malformed,
+    Expired,
+    WrongClientId,
+    InsufficientScope,
+    Unavailable,
+}
+
+impl AccessTokenError {
+    pub fn status(&self) -> Status {
+        match self {
+            AccessTokenError::InsufficientScope => Status::Forbidden,
+            AccessTokenError::Unavailable => Status::ServiceUnavailable,
+            _ => Status::Unauthorized,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AccessTokenError::Missing => "missing",
+            AccessTokenError::Malformed => "malformed",
+            AccessTokenError::Expired => "expired",
+            AccessTokenError::WrongClientId => "wrong_client_id",
+            AccessTokenError::InsufficientScope => "insufficient_scope",
+            AccessTokenError::Unavailable => "unavailable",
+        }
+    }
 
-pub async fn authenticate_twitch_user(access_token: &str) -> Result<TokenResponse, TokenResponse> {
+    pub fn message(&self) -> &'static str {
+        match self {
+            AccessTokenError::Missing => "no access token was sent",
+            AccessTokenError::Malformed => {
+                "expected a header of the form `Authorization: Bearer <token>`"
+            }
+            AccessTokenError::Expired => "the access token is expired or was revoked",
+            AccessTokenError::WrongClientId => {
+                "the access token was issued to a different client id"
+            }
+            AccessTokenError::InsufficientScope => "the access token is missing a required scope",
+            AccessTokenError::Unavailable => "the access token could not be validated with twitch",
+        }
+    }
+
+    /// RFC 6750 error code used in the `WWW-Authenticate` challenge.
+    pub fn oauth_error(&self) -> Option<&'static str> {
+        match self {
+            AccessTokenError::Missing | AccessTokenError::Unavailable => None,
+            AccessTokenError::Malformed => Some("invalid_request"),
+            AccessTokenError::Expired | AccessTokenError::WrongClientId => Some("invalid_token"),
+            AccessTokenError::InsufficientScope => Some("insufficient_scope"),
+        }
+    }
+}
+
+/// Remembers why the guard failed so catchers can report it.
+pub struct AccessTokenFailure(pub Option<AccessTokenError>);
+
+pub async fn authenticate_twitch_user(
+    access_token: &str,
+) -> Result<TwitchValidateToken, AccessTokenError> {
     let request = isahc::Request::builder()
         .uri("https://id.twitch.tv/oauth2/validate")
         .method("GET")
         .header("Authorization", format!("OAuth {}", access_token))
         .body(())
-        .map_err(|_| {
-            Outcome::<AccessTokenResponse, (Status, AccessTokenError), ()>::Failure((
-                Status::Unauthorized,
-                AccessTokenError::Invalid,
-            ))
-        })?;
-
-    let mut response = isahc::send_async(request).await.map_err(|_| {
-        Outcome::<AccessTokenResponse, (Status, AccessTokenError), ()>::Failure((
-            Status::Unauthorized,
-            AccessTokenError::Invalid,
-        ))
-    })?;
-
-    let json_response = response.json::<TwitchValidateToken>().await.map_err(|_| {
-        Outcome::<AccessTokenResponse, (Status, AccessTokenError), ()>::Failure((
-            Status::Unauthorized,
-            AccessTokenError::Invalid,
-        ))
-    })?;
-
-    Ok(Outcome::Success(AccessTokenResponse {
-        validate_token: json_response,
-        token: access_token.to_string(),
-    }))
+        .map_err(|_| AccessTokenError::Malformed)?;
+
+    let mut response = send("oauth2/validate", Bucket::Other, request)
+        .await
+        .map_err(|_| AccessTokenError::Unavailable)?;
+
+    match response.status() {
+        StatusCode::OK => response
+            .json::<TwitchValidateToken>()
+            .await
+            .map_err(|_| AccessTokenError::Unavailable),
+        StatusCode::UNAUTHORIZED => Err(AccessTokenError::Expired),
+        _ => Err(AccessTokenError::Unavailable),
+    }
 }
 
-pub fn is_token_valid(token: &str) -> bool {
-    let request_token: Vec<&str> = token.split(' ').collect();
-    request_token.starts_with(&["Bearer"])
+pub fn parse_bearer_token(header: &str) -> Option<&str> {
+    let mut parts = header.trim().splitn(2, ' ');
+    let scheme = parts.next()?;
+    let token = parts.next()?.trim();
+
+    match scheme.eq_ignore_ascii_case("Bearer") && !token.is_empty() && !token.contains(' ') {
+        true => Some(token),
+        false => None,
+    }
 }
-#[derive(Debug)]
-pub struct AccessToken(pub String);
 
-#[derive(Debug)]
-pub enum AccessTokenError {
-    Missing,
-    Invalid,
+pub fn check_validated_token(
+    validate_token: &TwitchValidateToken,
+    config: &GlobalConfig,
+) -> Result<(), AccessTokenError> {
+    if validate_token.expires_in == 0 {
+        return Err(AccessTokenError::Expired);
+    }
+
+    if !config.is_accepted_client_id(&validate_token.client_id) {
+        info!(
+            login = %validate_token.login,
+            client_id = %validate_token.client_id,
+            "rejecting token issued to another client id"
+        );
+        return Err(AccessTokenError::WrongClientId);
+    }
+
+    let has_scopes = config
+        .required_scopes
+        .iter()
+        .all(|scope| validate_token.scopes.contains(scope));
+
+    match has_scopes {
+        true => Ok(()),
+        false => Err(AccessTokenError::InsufficientScope),
+    }
+}
+
+async fn validate_request(req: &Request<'_>) -> Result<AccessTokenResponse, AccessTokenError> {
+    let authorization = req.headers().get("Authorization").collect::<Vec<&str>>();
+    // `token` is the header our frontends sent before `Authorization` was supported.
+    let legacy = req.headers().get("token").collect::<Vec<&str>>();
+
+    let config = req
+        .rocket()
+        .state::<GlobalConfig>()
+        .ok_or(AccessTokenError::Unavailable)?;
+
+    let header = match (authorization.len(), legacy.len()) {
+        (0, 0) => None,
+        (1, _) => Some(authorization[0]),
+        (0, 1) => Some(legacy[0]),
+        _ => return Err(AccessTokenError::Malformed),
+    };
+
+    // without a header, fall back to the session from `/auth/twitch/login`
+    let token = match header {
+        Some(header) => parse_bearer_token(header)
+            .ok_or(AccessTokenError::Malformed)?
+            .to_owned(),
+        None => session_access_token(req.cookies(), config).await?,
+    };
+
+    let validate_token = authenticate_twitch_user(&token).await?;
+    check_validated_token(&validate_token, config)?;
+
+    Ok(AccessTokenResponse {
+        validate_token,
+        token,
+    })
 }
 
 #[rocket::async_trait]
@@ -70,18 +186,38 @@ impl<'r> FromRequest<'r> for AccessTokenResponse {
     type Error = AccessTokenError;
 
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        let keys = req.headers().get("token").collect::<Vec<&str>>();
-
-        match keys.len() {
-            0 => Outcome::Failure((Status::Unauthorized, AccessTokenError::Missing)),
-            1 if is_token_valid(keys[0]) => {
-                let request_token: Vec<&str> = keys[0].split(' ').collect();
-                match authenticate_twitch_user(request_token[1]).await {
-                    Ok(v) => v,
-                    Err(e) => e,
+        match validate_request(req).await {
+            Ok(response) => Outcome::Success(response),
+            Err(e) => {
+                if e != AccessTokenError::Missing {
+                    USER_TOKENS_REJECTED.with_label_values(&[e.code()]).inc();
                 }
+                req.local_cache(|| AccessTokenFailure(Some(e)));
+                Outcome::Failure((e.status(), e))
             }
-            _ => Outcome::Failure((Status::Unauthorized, AccessTokenError::Invalid)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_bearer_token() {
+        assert_eq!(parse_bearer_token("Bearer abc123"), Some("abc123"));
+        assert_eq!(parse_bearer_token("bearer abc123"), Some("abc123"));
+        assert_eq!(parse_bearer_token("  Bearer   abc123  "), Some("abc123"));
+    }
+
+    #[test]
+    fn rejects_other_schemes_and_malformed_headers() {
+        assert_eq!(parse_bearer_token("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(parse_bearer_token("OAuth abc123"), None);
+        assert_eq!(parse_bearer_token("abc123"), None);
+        assert_eq!(parse_bearer_token("Bearer"), None);
+        assert_eq!(parse_bearer_token("Bearer "), None);
+        assert_eq!(parse_bearer_token("Bearer abc 123"), None);
+        assert_eq!(parse_bearer_token(""), None);
+    }
+}
@@ -0,0 +1,25 @@
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_FILTER: &str = "info,rocket=warn,isahc=warn";
+
+/// Installs the global subscriber from `log_format` ("text" or "json") and `log_filter`.
+///
+/// `RUST_LOG`, when set, takes precedence over `log_filter`.
+pub fn init(format: &str, filter: Option<&str>) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(filter.unwrap_or(DEFAULT_FILTER)));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = match format {
+        "json" => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+        _ => builder.try_init(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("failed to install the log subscriber: {}", e);
+    }
+}
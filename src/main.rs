@@ -1,74 +1,224 @@
-use rocket::{catchers, debug, info, launch, routes, Build, Rocket};
+use rocket::{catchers, launch, routes, Build, Rocket};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
 
 use catchers::not_found;
-use category::get_twitch_tag_ids;
+use config::Secret;
+use creators::CreatorRegistry;
+use denylist::Denylist;
 use routes::streams::get_streams;
 use routes::{stream::get_stream, streams::fetch_streams_interval};
-use states::GlobalConfig;
+use poller::{PollControl, Poller};
+use providers::ProviderStore;
+use settings::{ProviderBuilder, Settings, SettingsStore, SettingsWatcher};
+use snapshot::{SnapshotStore, StreamsSnapshot};
+use states::{AppToken, GlobalConfig};
+use utils::ListingRules;
 
-use crate::catchers::unauthorized;
-use crate::clients::twitch::get_all_tags_map;
+use crate::catchers::{forbidden, service_unavailable, too_many_requests, unauthorized};
+use crate::fairings::compression::Compression;
+use crate::fairings::cors::Cors;
+use crate::fairings::metrics::RequestMetrics;
+use crate::fairings::request_id::{traced, RequestIds};
+use crate::fairings::shutdown::GracefulShutdown;
+use crate::guards::api_key::ApiKeys;
+use crate::routes::admin::{
+    add_to_denylist, get_api_key_usage, get_config, get_denylist, refresh_streams, refresh_tags,
+    remove_from_denylist, rotate_client_secret, set_categories, set_poll_config,
+};
+use crate::routes::auth::{twitch_callback, twitch_login, twitch_logout};
 use crate::routes::follows::get_follows_for_user;
+use crate::routes::health::{get_liveness, get_readiness};
+use crate::routes::metrics::get_metrics;
 
 mod catchers;
 mod category;
 mod clients;
+mod config;
+mod creators;
+mod denylist;
+mod fairings;
 mod guards;
+mod logging;
+mod metrics;
+mod poller;
+mod providers;
 mod routes;
+mod session;
+mod settings;
+mod snapshot;
 mod states;
 mod utils;
 
 #[launch]
 async fn start() -> rocket::Rocket<Build> {
-    let rocket = Rocket::build();
+    let (figment, service) = config::load().unwrap_or_else(|e| panic!("invalid config: {}", e));
+    let rocket = Rocket::custom(figment);
     let figment = rocket.figment();
 
-    let client_id: String = figment.extract_inner("twitch_client_id").expect("custom");
-    let client_secret: String = figment
-        .extract_inner("twitch_client_secret")
-        .expect("custom");
+    logging::init(&service.log_format, service.log_filter.as_deref());
 
-    let tags = get_twitch_tag_ids();
-    let fetched_token = clients::twitch::get_token(&client_id, &client_secret);
+    // both are checked by config::load
+    let client_id: String = figment.extract_inner("twitch_client_id").unwrap();
+    let client_secret: Secret = figment.extract_inner("twitch_client_secret").unwrap();
 
-    debug!("token fetched at {:?}", fetched_token.access_token);
+    let settings = Settings::load(figment, service.settings_path.as_deref())
+        .unwrap_or_else(|e| panic!("invalid settings: {}", e));
+    // kept whole for /admin/config
+    let service_config = service.clone();
+    // serve, not ready, while the first token is fetched, so a twitch outage at boot
+    // doesn't crash-loop the service
+    let app_token = Arc::new(AppToken::new(client_id.clone(), client_secret));
+    {
+        let app_token = app_token.clone();
+        rocket::tokio::spawn(async move { app_token.fetch_at_startup().await });
+    }
 
-    let token = Arc::new(Mutex::new(fetched_token.clone()));
-    let expires_in = token.lock().unwrap().expires_in;
-    let expired = std::time::Duration::from_secs(expires_in);
-    let expiring_time = std::time::Instant::now() + expired;
+    // tag names are loaded through POST /admin/refresh/tags
+    let all_tags = HashMap::new();
+    // starting without a denylist that failed to load would list every denied channel again
+    let denylist = Arc::new(match &service.denylist_path {
+        Some(path) if path.exists() => Denylist::load(path.clone())
+            .unwrap_or_else(|e| panic!("failed to read the denylist at {}: {}", path.display(), e)),
+        _ => Denylist::new(service.denylist_path),
+    });
 
-    info!("token expiring at {:?}", expires_in);
+    let rules = Arc::new(ListingRules {
+        tags: settings.categories.clone(),
+        programming_game_ids: settings.programming_game_ids.clone(),
+        title_blocklist: settings.title_blocklist.clone(),
+        all_tags,
+        creators: CreatorRegistry::new(service.creators),
+        denylist: denylist.clone(),
+    });
 
-    let stream_fetch_interval =
-        rocket::tokio::time::interval(rocket::tokio::time::Duration::from_millis(15_000));
+    let snapshot = match service.snapshot_path.as_deref().filter(|path| path.exists()) {
+        Some(path) => match StreamsSnapshot::load(path, rules.clone()) {
+            Ok(snapshot) => {
+                info!(
+                    path = %path.display(),
+                    version = snapshot.version,
+                    "restored streams snapshot"
+                );
+                snapshot
+            }
+            Err(e) => {
+                warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "failed to restore streams snapshot"
+                );
+                StreamsSnapshot::empty(rules)
+            }
+        },
+        None => StreamsSnapshot::empty(rules),
+    };
+    let store = SnapshotStore::new(snapshot);
 
-    // let all_tags = get_all_tags_map(&client_id, &fetched_token.access_token).await;
-    let all_tags = HashMap::new();
+    let mut provider_builder = ProviderBuilder::new(client_id.clone(), app_token.clone());
+    let providers = ProviderStore::new(provider_builder.build(&settings));
+
+    let poll_control = Arc::new(PollControl::new(settings.poll.clone()));
+    let settings = SettingsStore::new(settings);
+
+    if let Some(path) = service.settings_path {
+        info!(path = %path.display(), "watching the settings file");
+        rocket::tokio::spawn(
+            SettingsWatcher {
+                figment: figment.clone(),
+                path,
+                interval: Duration::from_secs(service.settings_watch_interval.max(1)),
+                settings: settings.clone(),
+                provider_builder,
+                poll_control: poll_control.clone(),
+                snapshots: store.clone(),
+                providers: providers.clone(),
+            }
+            .run(),
+        );
+    }
+
+    let poller = {
+        let store = store.clone();
+        let providers = providers.clone();
+        let poll_control = poll_control.clone();
+        let app_token = app_token.clone();
 
-    rocket::tokio::spawn(fetch_streams_interval(
-        stream_fetch_interval,
-        client_id.clone(),
-        client_secret.clone(),
-        fetched_token,
-    ));
+        Arc::new(Poller::spawn(move || {
+            let (store, poll_control, providers) =
+                (store.clone(), poll_control.clone(), providers.clone());
+            let app_token = app_token.clone();
+
+            async move {
+                // crawling without a token would only fail
+                app_token.startup_fetched().await;
+                fetch_streams_interval(store, poll_control, providers).await
+            }
+        }))
+    };
 
     let config = GlobalConfig {
         client_id,
-        client_secret,
-        tags,
-        token,
-        expired: Arc::new(Mutex::new(expiring_time)),
-        all_tags
+        app_token,
+        required_scopes: service.twitch_required_scopes,
+        allowed_client_ids: service.twitch_allowed_client_ids,
+        redirect_uri: service.twitch_redirect_uri,
+        login_scopes: service.twitch_login_scopes,
+        login_redirect: service.twitch_login_redirect,
+        admin_token: service.admin_token,
+        max_snapshot_age: Duration::from_secs(service.readiness_max_snapshot_age),
+        poller: poller.status.clone(),
     };
 
     rocket
         .mount(
             "/stream-collection",
-            routes![get_streams, get_stream, get_follows_for_user],
+            traced(routes![
+                get_streams,
+                get_stream,
+                get_follows_for_user,
+                twitch_login,
+                twitch_callback,
+                twitch_logout,
+                get_api_key_usage,
+                get_denylist,
+                add_to_denylist,
+                remove_from_denylist,
+                get_config,
+                refresh_streams,
+                refresh_tags,
+                set_poll_config,
+                set_categories,
+                rotate_client_secret
+            ]),
+        )
+        .mount(
+            "/",
+            traced(routes![get_metrics, get_liveness, get_readiness]),
         )
         .manage(config)
-        .register("/", catchers![not_found, unauthorized])
+        .manage(service_config)
+        .manage(settings)
+        .manage(store.clone())
+        .manage(providers)
+        .manage(denylist)
+        .manage(poll_control)
+        .manage(ApiKeys::new(service.api_keys, service.api_key_required))
+        .attach(RequestIds)
+        .attach(RequestMetrics)
+        .attach(Cors::new(service.cors))
+        .attach(Compression::new(service.compression_min_size))
+        .attach(GracefulShutdown::new(poller, store, service.snapshot_path))
+        .register(
+            "/",
+            catchers![
+                not_found,
+                unauthorized,
+                forbidden,
+                too_many_requests,
+                service_unavailable
+            ],
+        )
 }
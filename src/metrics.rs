@@ -0,0 +1,241 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+
+pub static USER_TOKENS_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "user_tokens_rejected_total",
+        "Twitch user access tokens rejected by the auth guard",
+        &["reason"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Requests served, by route and response status",
+        &["route", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time taken to serve requests, by route and response status",
+        &["route", "status"]
+    )
+    .unwrap()
+});
+
+pub static TWITCH_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "twitch_api_requests_total",
+        "Requests made to the Twitch API, by endpoint and response status",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static TWITCH_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "twitch_api_request_duration_seconds",
+        "Time taken by requests to the Twitch API, by endpoint and response status",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static TWITCH_RATE_LIMIT_REMAINING: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "twitch_api_rate_limit_remaining",
+        "Helix rate limit points left in the current window"
+    )
+    .unwrap()
+});
+
+pub static TWITCH_RATE_LIMIT_RESET: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "twitch_api_rate_limit_reset_timestamp_seconds",
+        "Unix time the Helix rate limit bucket is next refilled"
+    )
+    .unwrap()
+});
+
+pub static YOUTUBE_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "youtube_api_requests_total",
+        "Requests made to the YouTube Data API, by endpoint and response status",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static YOUTUBE_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "youtube_api_request_duration_seconds",
+        "Time taken by requests to the YouTube Data API, by endpoint and response status",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static YOUTUBE_QUOTA_USED: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "youtube_api_quota_used",
+        "YouTube Data API quota units spent since the quota last reset"
+    )
+    .unwrap()
+});
+
+pub static KICK_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "kick_api_requests_total",
+        "Requests made to the Kick API, by endpoint and response status",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static KICK_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "kick_api_request_duration_seconds",
+        "Time taken by requests to the Kick API, by endpoint and response status",
+        &["endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static SETTINGS_RELOADS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "settings_reloads_total",
+        "Changes to the settings file, by whether they were applied or rejected",
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static LAST_SUCCESSFUL_POLL: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "streams_last_successful_poll_timestamp_seconds",
+        "Unix time of the last poll that replaced the streams snapshot"
+    )
+    .unwrap()
+});
+
+pub static LAST_SUCCESSFUL_POLL_AGE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "streams_last_successful_poll_age_seconds",
+        "Seconds since the streams snapshot was last replaced"
+    )
+    .unwrap()
+});
+
+pub static POLL_PAGES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "streams_poll_pages",
+        "Pages fetched for each provider source in the last poll",
+        &["platform", "source"]
+    )
+    .unwrap()
+});
+
+pub static LIVE_STREAMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "streams_live",
+        "Live streams in the current snapshot, by platform and category",
+        &["platform", "category"]
+    )
+    .unwrap()
+});
+
+pub static LISTED_STREAMS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "streams_listed",
+        "Streams served by each `/streams` listing in the current snapshot",
+        &["listing"]
+    )
+    .unwrap()
+});
+
+pub static APP_TOKEN_REFRESHES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "twitch_app_token_refreshes_total",
+        "Attempts to fetch a new Twitch app access token, by result",
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static POLL_INTERVAL: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "streams_poll_interval_seconds",
+        "Delay the poller chose before its next crawl"
+    )
+    .unwrap()
+});
+
+pub static POLL_TIMEOUTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "streams_poll_timeouts_total",
+        "Crawls abandoned because they overran the poll interval"
+    )
+    .unwrap()
+});
+
+pub static POLL_FAILURES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "streams_poll_failures_total",
+        "Crawls discarded because every source failed"
+    )
+    .unwrap()
+});
+
+pub static POLLER_UP: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "streams_poller_up",
+        "Whether the background stream poller is currently running"
+    )
+    .unwrap()
+});
+
+pub static POLLER_RESTARTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "streams_poller_restarts_total",
+        "Times the supervisor restarted the stream poller after it failed"
+    )
+    .unwrap()
+});
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub fn record_app_token_refresh(succeeded: bool) {
+    let result = match succeeded {
+        true => "success",
+        false => "failure",
+    };
+    APP_TOKEN_REFRESHES.with_label_values(&[result]).inc();
+}
+
+/// Renders every registered metric in the Prometheus text format.
+pub fn render() -> String {
+    let last_poll = LAST_SUCCESSFUL_POLL.get();
+    if last_poll > 0 {
+        LAST_SUCCESSFUL_POLL_AGE.set(unix_now() - last_poll);
+    }
+
+    let encoder = prometheus::TextEncoder::new();
+    encoder
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_default()
+}
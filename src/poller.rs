@@ -0,0 +1,300 @@
+use std::{
+    any::Any,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use arc_swap::ArcSwap;
+use rocket::tokio::{
+    self, select,
+    sync::{watch, Notify},
+    task::{JoinError, JoinHandle},
+    time::{sleep, sleep_until},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::metrics::{
+    unix_now, POLLER_RESTARTS, POLLER_UP, TWITCH_RATE_LIMIT_REMAINING, TWITCH_RATE_LIMIT_RESET,
+};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+fn default_min_interval() -> u64 {
+    15
+}
+
+fn default_max_interval() -> u64 {
+    120
+}
+
+fn default_max_pages() -> u64 {
+    50
+}
+
+fn default_grace_period() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PollConfig {
+    /// Seconds between crawls when they are quick and rate limit budget is plentiful.
+    #[serde(default = "default_min_interval")]
+    pub min_interval: u64,
+    /// Upper bound on the adaptive interval, also the longest a crawl may run.
+    #[serde(default = "default_max_interval")]
+    pub max_interval: u64,
+    /// Pages of 100 streams fetched per category before a crawl stops early.
+    #[serde(default = "default_max_pages")]
+    pub max_pages: u64,
+    /// Seconds a stream missing from a crawl is kept in the snapshot before it is dropped.
+    #[serde(default = "default_grace_period")]
+    pub grace_period: u64,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self {
+            min_interval: default_min_interval(),
+            max_interval: default_max_interval(),
+            max_pages: default_max_pages(),
+            grace_period: default_grace_period(),
+        }
+    }
+}
+
+impl PollConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_interval == 0 {
+            return Err("poll.min_interval must be at least 1 second".to_owned());
+        }
+        if self.max_pages == 0 {
+            return Err("poll.max_pages must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn min_interval(&self) -> Duration {
+        Duration::from_secs(self.min_interval)
+    }
+
+    pub fn max_interval(&self) -> Duration {
+        Duration::from_secs(self.max_interval.max(self.min_interval))
+    }
+
+    /// Picks the delay before the next crawl from how the last one went.
+    ///
+    /// The api is left idle for at least as long as the crawl took, and when the rate limit
+    /// bucket can't cover another crawl of `pages` requests we wait for it to refill.
+    pub fn next_interval(&self, crawl_duration: Duration, pages: u64) -> Duration {
+        let mut interval = self.min_interval().max(crawl_duration * 2);
+
+        let remaining = TWITCH_RATE_LIMIT_REMAINING.get();
+        let until_reset = TWITCH_RATE_LIMIT_RESET.get() - unix_now();
+        if until_reset > 0 && remaining < (pages * 2) as i64 {
+            interval = interval.max(Duration::from_secs(until_reset as u64));
+        }
+
+        interval.min(self.max_interval())
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        Duration::from_secs(self.grace_period)
+    }
+
+    /// Delay after a crawl that overran and was abandoned.
+    pub fn backoff_interval(&self, interval: Duration) -> Duration {
+        (interval * 2).min(self.max_interval())
+    }
+}
+
+/// Lets the admin routes steer the poll loop while it runs.
+pub struct PollControl {
+    config: ArcSwap<PollConfig>,
+    refresh: Notify,
+}
+
+impl PollControl {
+    pub fn new(config: PollConfig) -> Self {
+        Self {
+            config: ArcSwap::from_pointee(config),
+            refresh: Notify::new(),
+        }
+    }
+
+    /// The settings the next poll cycle runs with.
+    pub fn config(&self) -> Arc<PollConfig> {
+        self.config.load_full()
+    }
+
+    pub fn set_config(&self, config: PollConfig) {
+        self.config.store(Arc::new(config));
+    }
+
+    /// Cuts the wait before the next poll cycle short. Asked for mid-crawl, the next cycle
+    /// starts as soon as the current one finishes.
+    pub fn refresh_now(&self) {
+        self.refresh.notify_one();
+    }
+
+    /// Waits until `deadline`, or until a refresh is asked for.
+    pub async fn wait_until(&self, deadline: Instant) {
+        select! {
+            _ = sleep_until(deadline.into()) => {}
+            _ = self.refresh.notified() => info!("poll cycle requested"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PollerState {
+    Starting,
+    Running,
+    BackingOff,
+    Stopped,
+}
+
+/// What the supervisor knows about the background poller.
+pub struct PollerStatus {
+    state: Mutex<PollerState>,
+    restarts: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl PollerStatus {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(PollerState::Starting),
+            restarts: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub fn state(&self) -> PollerState {
+        *self.state.lock().unwrap()
+    }
+
+    pub fn restarts(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    fn set_state(&self, state: PollerState) {
+        *self.state.lock().unwrap() = state;
+        POLLER_UP.set((state == PollerState::Running) as i64);
+    }
+
+    fn record_failure(&self, error: String) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+        POLLER_RESTARTS.inc();
+        *self.last_error.lock().unwrap() = Some(error);
+    }
+}
+
+/// Runs the stream poller, restarting it with backoff whenever it panics or exits.
+pub struct Poller {
+    pub status: Arc<PollerStatus>,
+    shutdown: watch::Sender<bool>,
+    supervisor: Mutex<Option<JoinHandle<()>>>,
+}
+
+fn panic_message(e: JoinError) -> String {
+    if !e.is_panic() {
+        return e.to_string();
+    }
+
+    let payload: Box<dyn Any + Send> = e.into_panic();
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "poller panicked".to_owned(),
+        },
+    }
+}
+
+impl Poller {
+    /// Spawns the supervisor; `poll` builds a fresh poller future for every (re)start.
+    pub fn spawn<F, Fut>(poll: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let status = Arc::new(PollerStatus::new());
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+
+        let supervisor_status = status.clone();
+        let supervisor = tokio::spawn(async move {
+            let status = supervisor_status;
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                status.set_state(PollerState::Running);
+                let started_at = Instant::now();
+                let mut task = tokio::spawn(poll());
+
+                let error = select! {
+                    result = &mut task => match result {
+                        Ok(()) => "poller exited".to_owned(),
+                        Err(e) => panic_message(e),
+                    },
+                    _ = shutdown_rx.changed() => {
+                        // the snapshot swap never spans an await, so aborting can't tear it
+                        task.abort();
+                        let _ = task.await;
+                        break;
+                    }
+                };
+
+                if started_at.elapsed() > MAX_BACKOFF {
+                    backoff = INITIAL_BACKOFF;
+                }
+
+                error!(
+                    error = %error,
+                    backoff_secs = backoff.as_secs(),
+                    "stream poller failed, restarting"
+                );
+                status.record_failure(error);
+                status.set_state(PollerState::BackingOff);
+
+                select! {
+                    _ = sleep(backoff) => {}
+                    _ = shutdown_rx.changed() => break,
+                }
+
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+
+            status.set_state(PollerState::Stopped);
+            info!("stream poller stopped");
+        });
+
+        Self {
+            status,
+            shutdown,
+            supervisor: Mutex::new(Some(supervisor)),
+        }
+    }
+
+    /// Cancels any in-flight poll and waits for the supervisor to exit.
+    pub async fn stop(&self) {
+        let _ = self.shutdown.send(true);
+
+        let supervisor = self.supervisor.lock().unwrap().take();
+        if let Some(supervisor) = supervisor {
+            if let Err(e) = supervisor.await {
+                warn!(error = %e, "stream poller supervisor did not stop cleanly");
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{http::Status, tokio::sync::Mutex as AsyncMutex};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use super::{Channel, Inclusion, LiveStream, Platform, Source, SourceCrawl, StreamProvider};
+use crate::{
+    clients::kick::{
+        get_category_livestreams, get_channel, get_token, search_categories, KickChannel,
+        KickLivestream, KickToken,
+    },
+    config::Secret,
+    snapshot::CrawledStream,
+};
+
+/// Read from the `kick` config key; the provider is only enabled when it is set.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct KickConfig {
+    /// Client credentials for an app access token. Requests are sent without one when unset.
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret>,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    #[serde(default = "default_auth_url")]
+    pub auth_url: String,
+    /// Category names, resolved to ids on the first crawl.
+    #[serde(default = "default_categories")]
+    pub categories: Vec<String>,
+}
+
+impl KickConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_id.is_some() != self.client_secret.is_some() {
+            return Err("kick.client_id and kick.client_secret must be set together".to_owned());
+        }
+        if self.categories.is_empty() {
+            return Err("kick.categories is empty".to_owned());
+        }
+        Ok(())
+    }
+}
+
+fn default_base_url() -> String {
+    "https://api.kick.com".to_owned()
+}
+
+fn default_auth_url() -> String {
+    "https://id.kick.com".to_owned()
+}
+
+fn default_categories() -> Vec<String> {
+    vec!["Software Development".to_owned()]
+}
+
+/// The Kick app access token, fetched on first use and replaced once expired.
+struct KickAppToken {
+    auth_url: String,
+    client_id: String,
+    client_secret: Secret,
+    token: AsyncMutex<Option<(KickToken, Instant)>>,
+}
+
+impl KickAppToken {
+    async fn fetch_access_token(&self) -> Option<String> {
+        // held across the refresh, so concurrent crawls wait for one fetch instead of each
+        // making their own
+        let mut token = self.token.lock().await;
+
+        let is_expired = token
+            .as_ref()
+            .is_none_or(|(_, expires_at)| Instant::now() >= *expires_at);
+
+        if is_expired {
+            info!("kick app token expired, fetching a new one");
+            match get_token(&self.auth_url, &self.client_id, self.client_secret.expose()).await {
+                Ok(token_response) => {
+                    let expires_at =
+                        Instant::now() + Duration::from_secs(token_response.expires_in);
+                    *token = Some((token_response, expires_at));
+                }
+                // keep using the old token, the next request retries
+                Err(status) => warn!(%status, "kick app token refresh failed"),
+            }
+        }
+
+        token.as_ref().map(|(token, _)| token.access_token.clone())
+    }
+}
+
+pub struct KickProvider {
+    base_url: String,
+    app_token: Option<KickAppToken>,
+    sources: Vec<Source>,
+    category_ids: Mutex<HashMap<String, u64>>,
+}
+
+impl KickProvider {
+    pub fn new(config: KickConfig) -> Self {
+        let app_token = match (config.client_id, config.client_secret) {
+            (Some(client_id), Some(client_secret)) => Some(KickAppToken {
+                auth_url: config.auth_url,
+                client_id,
+                client_secret,
+                token: AsyncMutex::new(None),
+            }),
+            _ => None,
+        };
+
+        let sources = config
+            .categories
+            .into_iter()
+            .map(|category| Source {
+                name: category.to_lowercase().replace(' ', "_"),
+                id: category,
+            })
+            .collect();
+
+        Self {
+            base_url: config.base_url,
+            app_token,
+            sources,
+            category_ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn access_token(&self) -> Option<String> {
+        match &self.app_token {
+            Some(app_token) => app_token.fetch_access_token().await,
+            None => None,
+        }
+    }
+
+    async fn category_id(&self, name: &str, access_token: Option<&str>) -> Result<u64, Status> {
+        if let Some(id) = self.category_ids.lock().unwrap().get(name) {
+            return Ok(*id);
+        }
+
+        let id = search_categories(&self.base_url, access_token, name)
+            .await?
+            .data
+            .into_iter()
+            .find(|category| category.name.eq_ignore_ascii_case(name))
+            .map(|category| category.id)
+            .ok_or(Status::NotFound)?;
+
+        debug!(category = name, id, "category_id: resolved kick category");
+        self.category_ids
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), id);
+
+        Ok(id)
+    }
+}
+
+impl From<KickLivestream> for LiveStream {
+    fn from(stream: KickLivestream) -> Self {
+        Self {
+            platform: Platform::Kick,
+            id: stream.broadcaster_user_id.to_string(),
+            user_id: stream.broadcaster_user_id.to_string(),
+            url: Platform::Kick.channel_url(&stream.slug),
+            user_name: stream.slug.clone(),
+            user_login: stream.slug,
+            game_id: stream.category.id.to_string(),
+            game_name: stream.category.name,
+            title: stream.stream_title,
+            language: stream.language,
+            started_at: stream.started_at,
+            thumbnail_url: stream.thumbnail,
+            viewer_count: stream.viewer_count,
+            tag_ids: None,
+            r#type: "live".to_owned(),
+            included_by: Inclusion::Crawl,
+            creator_id: None,
+            simulcasts: vec![],
+        }
+    }
+}
+
+/// Maps a channel to its stream, `None` when it is offline.
+fn channel_live_stream(channel: KickChannel) -> Option<LiveStream> {
+    let stream = channel.stream.filter(|stream| stream.is_live)?;
+    let category = channel.category;
+
+    Some(LiveStream {
+        platform: Platform::Kick,
+        id: channel.broadcaster_user_id.to_string(),
+        user_id: channel.broadcaster_user_id.to_string(),
+        url: Platform::Kick.channel_url(&channel.slug),
+        user_name: channel.slug.clone(),
+        user_login: channel.slug,
+        game_id: category
+            .as_ref()
+            .map(|category| category.id.to_string())
+            .unwrap_or_default(),
+        game_name: category.map(|category| category.name).unwrap_or_default(),
+        title: channel.stream_title,
+        language: stream.language,
+        started_at: stream.start_time,
+        thumbnail_url: stream.thumbnail,
+        viewer_count: stream.viewer_count,
+        tag_ids: None,
+        r#type: "live".to_owned(),
+        included_by: Inclusion::Crawl,
+        creator_id: None,
+        simulcasts: vec![],
+    })
+}
+
+#[rocket::async_trait]
+impl StreamProvider for KickProvider {
+    fn platform(&self) -> Platform {
+        Platform::Kick
+    }
+
+    fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Fetches the 100 most watched streams in a category, as Kick serves a single page.
+    async fn live_streams(&self, source: &Source, _max_pages: u64) -> SourceCrawl {
+        let access_token = self.access_token().await;
+
+        let response = match self.category_id(&source.id, access_token.as_deref()).await {
+            Ok(category_id) => {
+                get_category_livestreams(&self.base_url, access_token.as_deref(), category_id).await
+            }
+            Err(status) => Err(status),
+        };
+
+        match response {
+            Ok(response) => {
+                let seen_at = Instant::now();
+                let streams: Vec<CrawledStream> = response
+                    .data
+                    .into_iter()
+                    .map(|stream| CrawledStream {
+                        stream: stream.into(),
+                        seen_at,
+                    })
+                    .collect();
+                debug!(
+                    source = %source.name,
+                    streams = streams.len(),
+                    "live_streams: fetched kick category"
+                );
+
+                SourceCrawl {
+                    streams,
+                    pages: 1,
+                    failed: false,
+                }
+            }
+            Err(status) => {
+                warn!(source = %source.name, %status, "live_streams: kick crawl failed");
+                SourceCrawl {
+                    streams: vec![],
+                    pages: 0,
+                    failed: true,
+                }
+            }
+        }
+    }
+
+    async fn channel_stream(&self, login: &str) -> Result<Option<LiveStream>, Status> {
+        let access_token = self.access_token().await;
+        let mut channels = get_channel(&self.base_url, access_token.as_deref(), login)
+            .await?
+            .data;
+        debug!(
+            channels = channels.len(),
+            "channel_stream: fetched kick channel"
+        );
+
+        Ok(match channels.len() {
+            1 => channel_live_stream(channels.swap_remove(0)),
+            _ => None,
+        })
+    }
+
+    async fn channel(&self, login: &str) -> Result<Channel, Status> {
+        let access_token = self.access_token().await;
+        let mut channels = get_channel(&self.base_url, access_token.as_deref(), login)
+            .await?
+            .data;
+        debug!(channels = channels.len(), "channel: fetched kick channel");
+
+        if channels.len() != 1 {
+            return Err(Status::NotFound);
+        }
+
+        let channel = channels.swap_remove(0);
+        Ok(Channel {
+            platform: Platform::Kick,
+            id: channel.broadcaster_user_id.to_string(),
+            display_name: channel.slug.clone(),
+            login: channel.slug,
+            description: channel.channel_description,
+            profile_image_url: String::new(),
+            offline_image_url: channel.banner_picture,
+            r#type: String::new(),
+            broadcaster_type: String::new(),
+            view_count: 0,
+        })
+    }
+}
@@ -0,0 +1,231 @@
+use std::{fmt, sync::Arc};
+
+use arc_swap::ArcSwap;
+
+use rocket::{http::Status, FromFormField};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clients::twitch::{user::TwitchUser, TwitchStream},
+    snapshot::CrawledStream,
+};
+
+mod kick;
+mod twitch;
+mod youtube;
+
+pub use kick::*;
+pub use twitch::*;
+pub use youtube::*;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, FromFormField, Default,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    #[default]
+    Twitch,
+    Youtube,
+    Kick,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Twitch => "twitch",
+            Platform::Youtube => "youtube",
+            Platform::Kick => "kick",
+        }
+    }
+
+    /// The page of the channel with `login`; YouTube logins are channel ids.
+    pub fn channel_url(&self, login: &str) -> String {
+        match self {
+            Platform::Twitch => format!("https://www.twitch.tv/{}", login),
+            Platform::Youtube => format!("https://www.youtube.com/channel/{}", login),
+            Platform::Kick => format!("https://kick.com/{}", login),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How a stream made it into the snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Inclusion {
+    /// Found crawling one of the provider's categories or searches.
+    #[default]
+    Crawl,
+    /// The channel is on a curated list that is tracked whatever it streams.
+    Curated,
+}
+
+/// A live stream on any platform. Field names follow Twitch's so existing clients keep working.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LiveStream {
+    /// Snapshots saved before streams carried a platform were all from Twitch.
+    #[serde(default)]
+    pub platform: Platform,
+    pub id: String,
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    /// Where the stream is watched. Empty in snapshots saved before streams carried one.
+    #[serde(default)]
+    pub url: String,
+    /// The platform's category (Twitch game) the stream is in.
+    pub game_id: String,
+    pub game_name: String,
+    pub title: String,
+    pub language: String,
+    pub started_at: String,
+    pub thumbnail_url: String,
+    pub viewer_count: u64,
+    pub tag_ids: Option<Vec<String>>,
+    pub r#type: String,
+    #[serde(default)]
+    pub included_by: Inclusion,
+    /// The registered creator the channel belongs to, set in listings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator_id: Option<String>,
+    /// The creator's other live streams, when a grouped listing folds them into this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub simulcasts: Vec<LiveStream>,
+}
+
+impl From<TwitchStream> for LiveStream {
+    fn from(stream: TwitchStream) -> Self {
+        Self {
+            platform: Platform::Twitch,
+            id: stream.id,
+            user_id: stream.user_id,
+            url: Platform::Twitch.channel_url(&stream.user_login),
+            user_login: stream.user_login,
+            user_name: stream.user_name,
+            game_id: stream.game_id,
+            game_name: stream.game_name,
+            title: stream.title,
+            language: stream.language,
+            started_at: stream.started_at,
+            thumbnail_url: stream.thumbnail_url,
+            viewer_count: stream.viewer_count,
+            tag_ids: stream.tag_ids,
+            r#type: stream.r#type,
+            included_by: Inclusion::Crawl,
+            creator_id: None,
+            simulcasts: vec![],
+        }
+    }
+}
+
+/// A channel (Twitch user) on any platform.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Channel {
+    pub platform: Platform,
+    pub id: String,
+    pub login: String,
+    pub display_name: String,
+    pub description: String,
+    pub profile_image_url: String,
+    pub offline_image_url: String,
+    #[serde(default)]
+    pub r#type: String,
+    #[serde(default)]
+    pub broadcaster_type: String,
+    #[serde(default)]
+    pub view_count: u32,
+}
+
+impl From<TwitchUser> for Channel {
+    fn from(user: TwitchUser) -> Self {
+        Self {
+            platform: Platform::Twitch,
+            id: user.id,
+            login: user.login,
+            display_name: user.display_name,
+            description: user.description,
+            profile_image_url: user.profile_image_url,
+            offline_image_url: user.offline_image_url,
+            r#type: user.r#type,
+            broadcaster_type: user.broadcaster_type,
+            view_count: user.view_count,
+        }
+    }
+}
+
+/// Somewhere a provider finds live streams, such as a Twitch category.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Source {
+    pub id: String,
+    /// Label used in logs and metrics.
+    pub name: String,
+}
+
+/// The streams one source returned in a poll.
+pub struct SourceCrawl {
+    pub streams: Vec<CrawledStream>,
+    pub pages: u64,
+    /// A request for the source failed, so `streams` holds only what was fetched before it.
+    pub failed: bool,
+}
+
+#[rocket::async_trait]
+pub trait StreamProvider: Send + Sync {
+    fn platform(&self) -> Platform;
+
+    /// Where the poller looks for live streams on this platform.
+    fn sources(&self) -> &[Source];
+
+    /// Fetches the live streams in `source`, reading at most `max_pages` pages.
+    async fn live_streams(&self, source: &Source, max_pages: u64) -> SourceCrawl;
+
+    /// Looks up the live stream of the channel with `login`, `None` when it is offline.
+    async fn channel_stream(&self, login: &str) -> Result<Option<LiveStream>, Status>;
+
+    /// Resolves a channel from its login.
+    async fn channel(&self, login: &str) -> Result<Channel, Status>;
+}
+
+/// Every enabled stream provider.
+#[derive(Clone, Default)]
+pub struct Providers(Vec<Arc<dyn StreamProvider>>);
+
+impl Providers {
+    pub fn with(mut self, provider: Arc<dyn StreamProvider>) -> Self {
+        self.0.push(provider);
+        self
+    }
+
+    pub fn get(&self, platform: Platform) -> Option<&Arc<dyn StreamProvider>> {
+        self.0
+            .iter()
+            .find(|provider| provider.platform() == platform)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn StreamProvider>> {
+        self.0.iter()
+    }
+}
+
+/// The providers in use, replaced when the settings file changes them.
+#[derive(Clone)]
+pub struct ProviderStore(Arc<ArcSwap<Providers>>);
+
+impl ProviderStore {
+    pub fn new(providers: Providers) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(providers)))
+    }
+
+    pub fn load(&self) -> Arc<Providers> {
+        self.0.load_full()
+    }
+
+    pub fn store(&self, providers: Providers) {
+        self.0.store(Arc::new(providers));
+    }
+}
@@ -0,0 +1,218 @@
+use std::{sync::Arc, time::Instant};
+
+use futures::future::join_all;
+use rocket::http::Status;
+use tracing::{debug, debug_span, warn, Instrument};
+
+use super::{Channel, Inclusion, LiveStream, Platform, Source, SourceCrawl, StreamProvider};
+use crate::{
+    clients::twitch::{get_game_streams, get_user_streams, user},
+    snapshot::CrawledStream,
+    states::AppToken,
+};
+
+/// Source holding the curated broadcasters, which are tracked whatever they stream.
+const TRACKED_SOURCE: &str = "tracked";
+
+/// Most broadcasters Helix takes in one `streams` request.
+const MAX_USER_IDS: usize = 100;
+
+/// The Twitch categories crawled when `twitch_sources` isn't configured.
+pub fn default_twitch_sources() -> Vec<Source> {
+    vec![
+        Source {
+            id: "509670".to_owned(),
+            name: "science_and_technology".to_owned(),
+        },
+        Source {
+            id: "1469308723".to_owned(),
+            name: "software_and_game_development".to_owned(),
+        },
+    ]
+}
+
+pub struct TwitchProvider {
+    client_id: String,
+    app_token: Arc<AppToken>,
+    sources: Vec<Source>,
+    /// Curated broadcaster ids, from `twitch_tracked_broadcasters`.
+    tracked: Vec<String>,
+}
+
+impl TwitchProvider {
+    pub fn new(
+        client_id: String,
+        app_token: Arc<AppToken>,
+        mut sources: Vec<Source>,
+        tracked: Vec<String>,
+    ) -> Self {
+        if !tracked.is_empty() {
+            sources.push(Source {
+                id: TRACKED_SOURCE.to_owned(),
+                name: TRACKED_SOURCE.to_owned(),
+            });
+        }
+
+        Self {
+            client_id,
+            app_token,
+            sources,
+            tracked,
+        }
+    }
+
+    /// Fetches the curated broadcasters' streams, 100 broadcasters a request.
+    ///
+    /// Unlike category pages these need no cursor, so the batches are fetched concurrently.
+    async fn tracked_streams(&self, max_pages: u64) -> SourceCrawl {
+        let access_token = self.app_token.fetch_access_token().await;
+
+        let batches: Vec<&[String]> = self
+            .tracked
+            .chunks(MAX_USER_IDS)
+            .take(max_pages as usize)
+            .collect();
+        if batches.len() < self.tracked.len().div_ceil(MAX_USER_IDS) {
+            warn!(
+                tracked = self.tracked.len(),
+                max_pages, "live_streams: tracked broadcasters past the page cap are skipped"
+            );
+        }
+
+        let responses = join_all(
+            batches
+                .iter()
+                .map(|user_ids| get_user_streams(&self.client_id, &access_token, user_ids)),
+        )
+        .await;
+
+        let failed = responses.iter().any(|response| response.is_err());
+        let seen_at = Instant::now();
+        let streams: Vec<CrawledStream> = responses
+            .into_iter()
+            .flatten()
+            .flat_map(|response| response.data)
+            .map(|stream| CrawledStream {
+                stream: LiveStream {
+                    included_by: Inclusion::Curated,
+                    ..stream.into()
+                },
+                seen_at,
+            })
+            .collect();
+        debug!(
+            streams = streams.len(),
+            "live_streams: fetched tracked broadcasters"
+        );
+
+        SourceCrawl {
+            streams,
+            pages: batches.len() as u64,
+            failed,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl StreamProvider for TwitchProvider {
+    fn platform(&self) -> Platform {
+        Platform::Twitch
+    }
+
+    fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Follows the cursor through every page of a category.
+    ///
+    /// Each page's cursor comes from the previous response, so pages are fetched one after
+    /// another; the poller crawls sources concurrently instead.
+    async fn live_streams(&self, source: &Source, max_pages: u64) -> SourceCrawl {
+        if source.id == TRACKED_SOURCE {
+            return self.tracked_streams(max_pages).await;
+        }
+
+        let access_token = self.app_token.fetch_access_token().await;
+        let mut streams = vec![];
+        let mut cursor = String::new();
+        let mut pages = 0;
+        let mut failed = false;
+
+        while pages < max_pages {
+            let span = debug_span!("page", source = %source.name, page = pages + 1);
+            let response = get_game_streams(&self.client_id, &access_token, &source.id, &cursor)
+                .instrument(span)
+                .await;
+            let response = match response {
+                Ok(response) => response,
+                Err(status) => {
+                    warn!(
+                        source = %source.name,
+                        page = pages + 1,
+                        %status,
+                        "live_streams: page request failed"
+                    );
+                    failed = true;
+                    break;
+                }
+            };
+
+            let seen_at = Instant::now();
+            streams.extend(response.data.into_iter().map(|stream| CrawledStream {
+                stream: stream.into(),
+                seen_at,
+            }));
+            pages += 1;
+
+            debug!(
+                source = %source.name,
+                streams = streams.len(),
+                "live_streams: fetched page"
+            );
+
+            cursor = match response.pagination.cursor {
+                Some(cursor) => cursor,
+                None => break,
+            };
+        }
+
+        if pages == max_pages {
+            warn!(
+                source = %source.name,
+                max_pages, "live_streams: stopped at the page cap"
+            );
+        }
+
+        SourceCrawl {
+            streams,
+            pages,
+            failed,
+        }
+    }
+
+    async fn channel_stream(&self, login: &str) -> Result<Option<LiveStream>, Status> {
+        let access_token = self.app_token.fetch_access_token().await;
+        let mut streams = user::get_stream(&self.client_id, &access_token, login)
+            .await?
+            .data;
+        debug!(streams = streams.len(), "channel_stream: fetched stream");
+
+        Ok(match streams.len() {
+            1 => Some(streams.swap_remove(0).into()),
+            _ => None,
+        })
+    }
+
+    async fn channel(&self, login: &str) -> Result<Channel, Status> {
+        let access_token = self.app_token.fetch_access_token().await;
+        let mut users = user::get_user(&self.client_id, &access_token, login)
+            .await?
+            .data;
+        debug!(users = users.len(), "channel: fetched user");
+
+        match users.len() {
+            1 => Ok(users.swap_remove(0).into()),
+            _ => Err(Status::NotFound),
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use rocket::http::Status;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, debug_span, info, warn, Instrument};
+
+use super::{Channel, Inclusion, LiveStream, Platform, Source, SourceCrawl, StreamProvider};
+use crate::{
+    clients::youtube::{
+        get_channel, get_recent_uploads, get_videos, search_live, YoutubeThumbnails, YoutubeVideo,
+        LIST_COST, MAX_VIDEO_IDS, SEARCH_COST,
+    },
+    config::Secret,
+    metrics::{unix_now, YOUTUBE_QUOTA_USED},
+    snapshot::CrawledStream,
+};
+
+/// Uploads checked per configured channel; a live broadcast is always among the newest.
+const UPLOADS_PER_CHANNEL: u64 = 5;
+
+/// Share of the daily quota kept for `/stream` lookups, which may spend no more than it so
+/// that anonymous lookups can't starve polling.
+const LOOKUP_QUOTA_PERCENT: u64 = 10;
+
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+const CHANNELS_SOURCE: &str = "channels";
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct YoutubeSearch {
+    /// Label used in logs and metrics.
+    pub name: String,
+    pub query: Option<String>,
+    pub category_id: Option<String>,
+}
+
+/// Read from the `youtube` config key; the provider is only enabled when it is set.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct YoutubeConfig {
+    pub api_key: Secret,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub searches: Vec<YoutubeSearch>,
+    /// Channels (`UC...` ids) listed whenever they are live, whatever they stream.
+    #[serde(default)]
+    pub channel_ids: Vec<String>,
+    #[serde(default = "default_daily_quota")]
+    pub daily_quota: u64,
+    #[serde(default = "default_search_pages")]
+    pub search_pages: u64,
+}
+
+impl YoutubeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.api_key.expose().is_empty() {
+            return Err("youtube.api_key is empty".to_owned());
+        }
+        if self.daily_quota == 0 || self.search_pages == 0 {
+            return Err(
+                "youtube.daily_quota and youtube.search_pages must be at least 1".to_owned(),
+            );
+        }
+        for search in &self.searches {
+            if search.query.is_none() && search.category_id.is_none() {
+                return Err(format!(
+                    "youtube search {:?} needs a query or a category_id",
+                    search.name
+                ));
+            }
+        }
+        if let Some(channel_id) = self.channel_ids.iter().find(|id| !id.starts_with("UC")) {
+            return Err(format!(
+                "youtube channel id {:?} isn't a UC... id",
+                channel_id
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn default_base_url() -> String {
+    "https://www.googleapis.com/youtube/v3".to_owned()
+}
+
+fn default_daily_quota() -> u64 {
+    10_000
+}
+
+fn default_search_pages() -> u64 {
+    1
+}
+
+/// What quota units are spent on. Each has its own share of the daily quota.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QuotaUse {
+    Poll,
+    Lookup,
+}
+
+/// The quota day and the units spent in it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct QuotaDay {
+    day: i64,
+    poll: u64,
+    lookup: u64,
+}
+
+impl QuotaDay {
+    fn new(day: i64) -> Self {
+        Self {
+            day,
+            poll: 0,
+            lookup: 0,
+        }
+    }
+}
+
+/// Counts the quota units spent today.
+struct QuotaTracker {
+    daily_quota: u64,
+    used: Mutex<QuotaDay>,
+}
+
+impl QuotaTracker {
+    fn new(daily_quota: u64) -> Self {
+        Self {
+            daily_quota,
+            used: Mutex::new(QuotaDay::new(Self::today())),
+        }
+    }
+
+    /// The quota resets at midnight Pacific time. Daylight saving is ignored, so from March
+    /// to November the day rolls over an hour late, which only errs on the side of caution.
+    fn today() -> i64 {
+        (unix_now() - 8 * 3600) / 86400
+    }
+
+    fn lookup_quota(&self) -> u64 {
+        self.daily_quota * LOOKUP_QUOTA_PERCENT / 100
+    }
+
+    fn try_spend(&self, purpose: QuotaUse, units: u64) -> bool {
+        self.try_spend_on(Self::today(), purpose, units)
+    }
+
+    fn try_spend_on(&self, day: i64, purpose: QuotaUse, units: u64) -> bool {
+        let mut used = self.used.lock().unwrap();
+        if used.day != day {
+            *used = QuotaDay::new(day);
+        }
+
+        let (spent, quota) = match purpose {
+            QuotaUse::Poll => (&mut used.poll, self.daily_quota - self.lookup_quota()),
+            QuotaUse::Lookup => (&mut used.lookup, self.lookup_quota()),
+        };
+        if *spent + units > quota {
+            return false;
+        }
+
+        *spent += units;
+        YOUTUBE_QUOTA_USED.set((used.poll + used.lookup) as i64);
+        true
+    }
+
+    /// Stops spending until tomorrow, after YouTube reports the quota as exceeded.
+    fn exhaust(&self) {
+        let mut used = self.used.lock().unwrap();
+        *used = QuotaDay {
+            day: Self::today(),
+            poll: self.daily_quota - self.lookup_quota(),
+            lookup: self.lookup_quota(),
+        };
+        YOUTUBE_QUOTA_USED.set(self.daily_quota as i64);
+    }
+}
+
+#[derive(Clone)]
+struct CachedSource {
+    fetched_at: Instant,
+    streams: Vec<LiveStream>,
+}
+
+/// Lists live streams from the YouTube Data API.
+///
+/// A search costs 100 quota units against a default daily quota of 10,000, so sources are
+/// refreshed on an interval derived from the quota and the cached streams are served to the
+/// polls in between.
+pub struct YoutubeProvider {
+    config: YoutubeConfig,
+    sources: Vec<Source>,
+    quota: QuotaTracker,
+    refresh_interval: Duration,
+    cache: Mutex<HashMap<String, CachedSource>>,
+}
+
+impl YoutubeProvider {
+    pub fn new(config: YoutubeConfig) -> Self {
+        let mut sources: Vec<Source> = config
+            .searches
+            .iter()
+            .map(|search| Source {
+                id: search.name.clone(),
+                name: search.name.clone(),
+            })
+            .collect();
+        if !config.channel_ids.is_empty() {
+            sources.push(Source {
+                id: CHANNELS_SOURCE.to_owned(),
+                name: CHANNELS_SOURCE.to_owned(),
+            });
+        }
+
+        let refresh_interval = Self::refresh_interval(&config);
+        info!(
+            refresh_interval_secs = refresh_interval.as_secs(),
+            daily_quota = config.daily_quota,
+            "youtube provider enabled"
+        );
+
+        Self {
+            quota: QuotaTracker::new(config.daily_quota),
+            config,
+            sources,
+            refresh_interval,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Builds a provider for new settings that carries on with this one's quota count and
+    /// with the cached streams of sources that are still configured.
+    pub fn reconfigure(&self, config: YoutubeConfig) -> Self {
+        let provider = Self::new(config);
+        *provider.quota.used.lock().unwrap() = *self.quota.used.lock().unwrap();
+
+        let cache = self.cache.lock().unwrap();
+        provider.cache.lock().unwrap().extend(
+            cache
+                .iter()
+                .filter(|(id, _)| provider.sources.iter().any(|source| &source.id == *id))
+                .map(|(id, cached)| (id.clone(), cached.clone())),
+        );
+
+        provider
+    }
+
+    /// How often every source can be refreshed without running out of quota before it resets.
+    fn refresh_interval(config: &YoutubeConfig) -> Duration {
+        let searches =
+            config.searches.len() as u64 * config.search_pages * (SEARCH_COST + LIST_COST);
+        let channels = config.channel_ids.len() as u64;
+        let channel_videos = (channels * UPLOADS_PER_CHANNEL).div_ceil(MAX_VIDEO_IDS as u64);
+        let cost = searches + (channels + channel_videos) * LIST_COST;
+
+        let budget = config.daily_quota * (100 - LOOKUP_QUOTA_PERCENT) / 100;
+        let interval = Duration::from_secs(86400 * cost / budget.max(1));
+        interval.max(MIN_REFRESH_INTERVAL)
+    }
+
+    fn spend(&self, purpose: QuotaUse, units: u64) -> Result<(), Status> {
+        match self.quota.try_spend(purpose, units) {
+            true => Ok(()),
+            false => Err(Status::TooManyRequests),
+        }
+    }
+
+    fn check_quota<T>(&self, result: Result<T, Status>) -> Result<T, Status> {
+        if let Err(status) = &result {
+            if status.code == Status::TooManyRequests.code {
+                self.quota.exhaust();
+            }
+        }
+        result
+    }
+
+    async fn search_ids(
+        &self,
+        search: &YoutubeSearch,
+        max_pages: u64,
+    ) -> Result<(Vec<String>, u64), Status> {
+        let mut ids = vec![];
+        let mut page_token = None;
+        let mut pages = 0;
+
+        while pages < self.config.search_pages.min(max_pages) {
+            self.spend(QuotaUse::Poll, SEARCH_COST)?;
+            let response = self.check_quota(
+                search_live(
+                    &self.config.base_url,
+                    self.config.api_key.expose(),
+                    search.query.as_deref(),
+                    search.category_id.as_deref(),
+                    page_token.as_deref(),
+                )
+                .await,
+            )?;
+            pages += 1;
+
+            ids.extend(
+                response
+                    .items
+                    .into_iter()
+                    .filter_map(|item| item.id.video_id),
+            );
+
+            page_token = match response.next_page_token {
+                Some(page_token) => Some(page_token),
+                None => break,
+            };
+        }
+
+        Ok((ids, pages))
+    }
+
+    /// The ids of the newest uploads of a channel, which include any live broadcast.
+    async fn upload_ids(&self, purpose: QuotaUse, channel_id: &str) -> Result<Vec<String>, Status> {
+        self.spend(purpose, LIST_COST)?;
+        let response = self.check_quota(
+            get_recent_uploads(
+                &self.config.base_url,
+                self.config.api_key.expose(),
+                channel_id,
+            )
+            .await,
+        )?;
+
+        Ok(response
+            .items
+            .into_iter()
+            .map(|item| item.content_details.video_id)
+            .collect())
+    }
+
+    async fn live_videos(
+        &self,
+        purpose: QuotaUse,
+        ids: &[String],
+    ) -> Result<Vec<LiveStream>, Status> {
+        let mut streams = vec![];
+
+        for chunk in ids.chunks(MAX_VIDEO_IDS) {
+            self.spend(purpose, LIST_COST)?;
+            let response = self.check_quota(
+                get_videos(&self.config.base_url, self.config.api_key.expose(), chunk).await,
+            )?;
+            streams.extend(response.items.into_iter().filter_map(to_live_stream));
+        }
+
+        Ok(streams)
+    }
+
+    async fn fetch_source(
+        &self,
+        source: &Source,
+        max_pages: u64,
+    ) -> Result<(Vec<LiveStream>, u64), Status> {
+        if source.id == CHANNELS_SOURCE {
+            let uploads = join_all(
+                self.config
+                    .channel_ids
+                    .iter()
+                    .map(|channel_id| self.upload_ids(QuotaUse::Poll, channel_id)),
+            )
+            .await;
+
+            let mut ids = vec![];
+            for (channel_id, result) in self.config.channel_ids.iter().zip(uploads) {
+                match result {
+                    Ok(uploads) => ids.extend(uploads),
+                    Err(status) if status.code == Status::TooManyRequests.code => {
+                        return Err(status)
+                    }
+                    Err(status) => {
+                        warn!(channel_id = %channel_id, %status, "youtube: failed to list uploads")
+                    }
+                }
+            }
+
+            let requests = self.config.channel_ids.len() + ids.len().div_ceil(MAX_VIDEO_IDS);
+            let streams = self
+                .live_videos(QuotaUse::Poll, &ids)
+                .await?
+                .into_iter()
+                .map(|stream| LiveStream {
+                    included_by: Inclusion::Curated,
+                    ..stream
+                })
+                .collect();
+            return Ok((streams, requests as u64));
+        }
+
+        let search = self
+            .config
+            .searches
+            .iter()
+            .find(|search| search.name == source.id)
+            .ok_or(Status::NotFound)?;
+
+        let (ids, pages) = self.search_ids(search, max_pages).await?;
+        Ok((self.live_videos(QuotaUse::Poll, &ids).await?, pages))
+    }
+}
+
+/// Names of the YouTube video categories programming streams turn up in.
+fn category_name(category_id: &str) -> &'static str {
+    match category_id {
+        "20" => "Gaming",
+        "22" => "People & Blogs",
+        "24" => "Entertainment",
+        "27" => "Education",
+        "28" => "Science & Technology",
+        _ => "",
+    }
+}
+
+fn thumbnail_url(thumbnails: YoutubeThumbnails) -> String {
+    thumbnails
+        .high
+        .or(thumbnails.medium)
+        .or(thumbnails.default)
+        .map(|thumbnail| thumbnail.url)
+        .unwrap_or_default()
+}
+
+/// Maps a video to a stream, `None` unless it is live right now.
+fn to_live_stream(video: YoutubeVideo) -> Option<LiveStream> {
+    let details = video.live_streaming_details?;
+    if video.snippet.live_broadcast_content != "live" || details.actual_end_time.is_some() {
+        return None;
+    }
+
+    let snippet = video.snippet;
+    let language = snippet
+        .default_audio_language
+        .or(snippet.default_language)
+        .and_then(|language| language.split('-').next().map(str::to_lowercase))
+        .unwrap_or_default();
+
+    Some(LiveStream {
+        platform: Platform::Youtube,
+        url: format!("https://www.youtube.com/watch?v={}", video.id),
+        id: video.id,
+        user_id: snippet.channel_id.clone(),
+        user_login: snippet.channel_id,
+        user_name: snippet.channel_title,
+        game_name: category_name(&snippet.category_id).to_owned(),
+        game_id: snippet.category_id,
+        title: snippet.title,
+        language,
+        started_at: details.actual_start_time.unwrap_or_default(),
+        thumbnail_url: thumbnail_url(snippet.thumbnails),
+        viewer_count: details
+            .concurrent_viewers
+            .and_then(|viewers| viewers.parse().ok())
+            .unwrap_or(0),
+        tag_ids: None,
+        r#type: "live".to_owned(),
+        included_by: Inclusion::Crawl,
+        creator_id: None,
+        simulcasts: vec![],
+    })
+}
+
+#[rocket::async_trait]
+impl StreamProvider for YoutubeProvider {
+    fn platform(&self) -> Platform {
+        Platform::Youtube
+    }
+
+    fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Serves the cached streams until the source is due a refresh, then fetches it again.
+    ///
+    /// A failed refresh keeps the cached streams and waits a whole interval before retrying,
+    /// as YouTube charges quota for failed requests too.
+    async fn live_streams(&self, source: &Source, max_pages: u64) -> SourceCrawl {
+        let now = Instant::now();
+
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&source.id)
+            .and_then(|cached| {
+                (now.duration_since(cached.fetched_at) < self.refresh_interval)
+                    .then(|| cached.streams.clone())
+            });
+
+        let (streams, pages, failed) = match cached {
+            Some(streams) => (streams, 0, false),
+            None => {
+                let span = debug_span!("youtube_source", source = %source.name);
+                let result = self.fetch_source(source, max_pages).instrument(span).await;
+
+                let mut cache = self.cache.lock().unwrap();
+                let cached = cache.entry(source.id.clone()).or_insert(CachedSource {
+                    fetched_at: now,
+                    streams: vec![],
+                });
+                cached.fetched_at = now;
+
+                match result {
+                    Ok((streams, pages)) => {
+                        debug!(
+                            source = %source.name,
+                            streams = streams.len(),
+                            "live_streams: refreshed youtube source"
+                        );
+                        cached.streams = streams;
+                        (cached.streams.clone(), pages, false)
+                    }
+                    Err(status) => {
+                        warn!(
+                            source = %source.name,
+                            %status,
+                            "live_streams: youtube refresh failed, serving cached streams"
+                        );
+                        (cached.streams.clone(), 0, true)
+                    }
+                }
+            }
+        };
+
+        // cached streams count as seen now, or the snapshot would drop them between refreshes
+        let seen_at = Instant::now();
+        SourceCrawl {
+            streams: streams
+                .into_iter()
+                .map(|stream| CrawledStream { stream, seen_at })
+                .collect(),
+            pages,
+            failed,
+        }
+    }
+
+    /// `login` is the channel id, which is what YouTube streams carry as their `user_login`.
+    async fn channel_stream(&self, login: &str) -> Result<Option<LiveStream>, Status> {
+        let ids = self.upload_ids(QuotaUse::Lookup, login).await?;
+        let mut streams = self.live_videos(QuotaUse::Lookup, &ids).await?;
+        debug!(
+            streams = streams.len(),
+            "channel_stream: fetched youtube stream"
+        );
+
+        Ok(streams.pop())
+    }
+
+    async fn channel(&self, login: &str) -> Result<Channel, Status> {
+        self.spend(QuotaUse::Lookup, LIST_COST)?;
+        let mut channels = self
+            .check_quota(
+                get_channel(&self.config.base_url, self.config.api_key.expose(), login).await,
+            )?
+            .items;
+        debug!(
+            channels = channels.len(),
+            "channel: fetched youtube channel"
+        );
+
+        if channels.len() != 1 {
+            return Err(Status::NotFound);
+        }
+
+        let channel = channels.swap_remove(0);
+        let login = match channel.snippet.custom_url {
+            Some(custom_url) => custom_url,
+            None => channel.id.clone(),
+        };
+        Ok(Channel {
+            platform: Platform::Youtube,
+            id: channel.id,
+            login,
+            display_name: channel.snippet.title,
+            description: channel.snippet.description,
+            profile_image_url: thumbnail_url(channel.snippet.thumbnails),
+            offline_image_url: String::new(),
+            r#type: String::new(),
+            broadcaster_type: String::new(),
+            view_count: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_are_capped_at_their_share() {
+        let quota = QuotaTracker::new(1000);
+
+        assert!(quota.try_spend_on(1, QuotaUse::Lookup, 100));
+        assert!(!quota.try_spend_on(1, QuotaUse::Lookup, 1));
+        assert!(quota.try_spend_on(1, QuotaUse::Poll, 900));
+        assert!(!quota.try_spend_on(1, QuotaUse::Poll, 1));
+    }
+
+    #[test]
+    fn spending_resets_when_the_day_rolls_over() {
+        let quota = QuotaTracker::new(1000);
+
+        assert!(quota.try_spend_on(1, QuotaUse::Poll, 900));
+        assert!(!quota.try_spend_on(1, QuotaUse::Poll, 100));
+        assert!(quota.try_spend_on(2, QuotaUse::Poll, 100));
+        assert_eq!(
+            *quota.used.lock().unwrap(),
+            QuotaDay {
+                day: 2,
+                poll: 100,
+                lookup: 0
+            }
+        );
+    }
+
+    #[test]
+    fn exhausting_blocks_both_shares() {
+        let quota = QuotaTracker::new(1000);
+        quota.exhaust();
+
+        assert!(!quota.try_spend(QuotaUse::Poll, 1));
+        assert!(!quota.try_spend(QuotaUse::Lookup, 1));
+    }
+}
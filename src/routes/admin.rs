@@ -0,0 +1,269 @@
+use std::{collections::HashMap, convert::TryFrom, sync::Arc};
+
+use rocket::{delete, get, http::Status, post, put, serde::json::Json, State};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    category::Category,
+    clients::twitch::get_all_tags_map,
+    config::{ServiceConfig, REDACTED},
+    denylist::{Denylist, DenylistEntry},
+    guards::{
+        admin::Admin,
+        api_key::{ApiKeyUsage, ApiKeys},
+    },
+    metrics::unix_now,
+    poller::{PollConfig, PollControl},
+    providers::Platform,
+    settings::{Settings, SettingsStore},
+    snapshot::{SnapshotStore, StreamsSnapshot},
+    states::GlobalConfig,
+    utils::{JsonResponse, ListingRules},
+};
+
+#[get("/admin/api-keys")]
+pub fn get_api_key_usage(
+    _admin: Admin,
+    api_keys: &State<ApiKeys>,
+) -> JsonResponse<Vec<ApiKeyUsage>> {
+    JsonResponse::new(api_keys.usage(), Status::Ok)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DenyBroadcaster {
+    #[serde(default)]
+    platform: Platform,
+    broadcaster_id: String,
+    reason: String,
+    actor: String,
+    /// Seconds until the entry expires, never when unset.
+    expires_in: Option<u64>,
+}
+
+#[get("/admin/denylist")]
+pub fn get_denylist(
+    _admin: Admin,
+    denylist: &State<Arc<Denylist>>,
+) -> JsonResponse<Vec<DenylistEntry>> {
+    JsonResponse::new(denylist.entries(), Status::Ok)
+}
+
+/// Hides a broadcaster from the listings, relisting the current snapshot straight away.
+#[post("/admin/denylist", data = "<deny>")]
+pub fn add_to_denylist(
+    _admin: Admin,
+    deny: Json<DenyBroadcaster>,
+    denylist: &State<Arc<Denylist>>,
+    snapshots: &State<SnapshotStore>,
+) -> Result<JsonResponse<DenylistEntry>, Status> {
+    let deny = deny.into_inner();
+    let now = unix_now();
+    // an expiry past what a unix timestamp holds would wrap into the past
+    let expires_at = match deny.expires_in {
+        Some(expires_in) => Some(
+            i64::try_from(expires_in)
+                .ok()
+                .and_then(|expires_in| now.checked_add(expires_in))
+                .ok_or(Status::UnprocessableEntity)?,
+        ),
+        None => None,
+    };
+    let entry = DenylistEntry {
+        platform: deny.platform,
+        broadcaster_id: deny.broadcaster_id,
+        reason: deny.reason,
+        actor: deny.actor,
+        added_at: now,
+        expires_at,
+    };
+
+    let saved = denylist.add(entry.clone());
+    snapshots.update(StreamsSnapshot::relist);
+
+    if let Err(e) = saved {
+        // the entry still applies, until the service restarts
+        error!(error = %e, "add_to_denylist: failed to save the denylist");
+        return Err(Status::InternalServerError);
+    }
+
+    info!(
+        platform = %entry.platform,
+        broadcaster_id = %entry.broadcaster_id,
+        actor = %entry.actor,
+        reason = %entry.reason,
+        "denied broadcaster"
+    );
+
+    Ok(JsonResponse::new(entry, Status::Created))
+}
+
+#[delete("/admin/denylist/<broadcaster_id>?<platform>")]
+pub fn remove_from_denylist(
+    _admin: Admin,
+    broadcaster_id: String,
+    platform: Option<Platform>,
+    denylist: &State<Arc<Denylist>>,
+    snapshots: &State<SnapshotStore>,
+) -> Result<Status, Status> {
+    let platform = platform.unwrap_or_default();
+
+    let removed = denylist.remove(platform, &broadcaster_id).map_err(|e| {
+        snapshots.update(StreamsSnapshot::relist);
+        error!(error = %e, "remove_from_denylist: failed to save the denylist");
+        Status::InternalServerError
+    })?;
+
+    match removed {
+        Some(entry) => {
+            snapshots.update(StreamsSnapshot::relist);
+            info!(
+                platform = %entry.platform,
+                broadcaster_id = %entry.broadcaster_id,
+                "removed broadcaster from the denylist"
+            );
+            Ok(Status::NoContent)
+        }
+        None => Err(Status::NotFound),
+    }
+}
+
+/// The settings the service is running with, keyed as in the config. Secrets are redacted.
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfig {
+    twitch_client_id: String,
+    twitch_client_secret: &'static str,
+    #[serde(flatten)]
+    service: ServiceConfig,
+    #[serde(flatten)]
+    settings: Settings,
+}
+
+#[get("/admin/config")]
+pub fn get_config(
+    _admin: Admin,
+    config: &State<GlobalConfig>,
+    service: &State<ServiceConfig>,
+    settings: &State<SettingsStore>,
+    poll_control: &State<Arc<PollControl>>,
+    snapshots: &State<SnapshotStore>,
+) -> JsonResponse<EffectiveConfig> {
+    // the admin routes change the poll settings and listing rules without touching the file
+    let mut settings = (*settings.load()).clone();
+    settings.poll = (*poll_control.config()).clone();
+    let snapshot = snapshots.load();
+    let rules = snapshot.rules();
+    settings.categories = rules.tags.clone();
+    settings.programming_game_ids = rules.programming_game_ids.clone();
+    settings.title_blocklist = rules.title_blocklist.clone();
+
+    let effective = EffectiveConfig {
+        twitch_client_id: config.client_id.clone(),
+        twitch_client_secret: REDACTED,
+        service: (*service).clone(),
+        settings,
+    };
+
+    JsonResponse::new(effective, Status::Ok)
+}
+
+/// Starts a poll cycle now instead of waiting out the interval.
+#[post("/admin/refresh")]
+pub fn refresh_streams(_admin: Admin, poll_control: &State<Arc<PollControl>>) -> Status {
+    poll_control.refresh_now();
+    Status::Accepted
+}
+
+/// Fetches the tag names listings show again.
+///
+/// Twitch has retired its tags endpoint, so an empty result keeps the names already loaded.
+#[post("/admin/refresh/tags")]
+pub async fn refresh_tags(
+    _admin: Admin,
+    config: &State<GlobalConfig>,
+    snapshots: &State<SnapshotStore>,
+) -> Result<JsonResponse<usize>, Status> {
+    let access_token = config.app_token.fetch_access_token().await;
+    let all_tags = get_all_tags_map(&config.client_id, &access_token).await;
+
+    if all_tags.is_empty() {
+        warn!("refresh_tags: twitch returned no tags, keeping the current ones");
+        return Err(Status::BadGateway);
+    }
+
+    let count = all_tags.len();
+    snapshots.update(|current| {
+        current.with_rules(Arc::new(ListingRules {
+            all_tags,
+            ..current.rules().clone()
+        }))
+    });
+    info!(tags = count, "refreshed twitch tags");
+
+    Ok(JsonResponse::new(count, Status::Ok))
+}
+
+/// Replaces the poll settings; the next poll cycle picks them up.
+#[put("/admin/config/poll", data = "<poll>")]
+pub fn set_poll_config(
+    _admin: Admin,
+    poll: Json<PollConfig>,
+    poll_control: &State<Arc<PollControl>>,
+) -> Result<JsonResponse<PollConfig>, JsonResponse<String>> {
+    let poll = poll.into_inner();
+    poll.validate()
+        .map_err(|e| JsonResponse::new(e, Status::UnprocessableEntity))?;
+
+    info!(?poll, "changed the poll settings");
+    poll_control.set_config(poll.clone());
+
+    Ok(JsonResponse::new(poll, Status::Ok))
+}
+
+/// Replaces the tag id each listing category matches, relisting the snapshot straight away.
+#[put("/admin/config/categories", data = "<categories>")]
+pub fn set_categories(
+    _admin: Admin,
+    categories: Json<HashMap<Category, String>>,
+    snapshots: &State<SnapshotStore>,
+) -> JsonResponse<HashMap<Category, String>> {
+    let tags = categories.into_inner();
+
+    snapshots.update(|current| {
+        current.with_rules(Arc::new(ListingRules {
+            tags: tags.clone(),
+            ..current.rules().clone()
+        }))
+    });
+    info!(categories = tags.len(), "changed the listing categories");
+
+    JsonResponse::new(tags, Status::Ok)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClientSecret {
+    client_secret: String,
+}
+
+/// Rotates the Twitch client secret, checking it by fetching a new app token with it.
+#[post("/admin/twitch/client-secret", data = "<secret>")]
+pub async fn rotate_client_secret(
+    _admin: Admin,
+    secret: Json<ClientSecret>,
+    config: &State<GlobalConfig>,
+) -> Status {
+    let client_secret = secret.into_inner().client_secret;
+
+    match config.app_token.rotate_client_secret(client_secret).await {
+        Ok(()) => Status::NoContent,
+        Err(status) if status == Status::Unauthorized => {
+            warn!("rotate_client_secret: twitch rejected the new secret");
+            Status::UnprocessableEntity
+        }
+        // twitch couldn't check the secret, which says nothing about whether it's right
+        Err(status) => {
+            warn!(%status, "rotate_client_secret: couldn't check the new secret");
+            status
+        }
+    }
+}
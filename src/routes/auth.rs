@@ -0,0 +1,73 @@
+use rocket::{get, http::CookieJar, http::Status, post, response::Redirect, State};
+use tracing::info;
+
+use crate::{
+    clients::twitch::{exchange_code, get_authorize_url},
+    session::{
+        clear_user_token, pkce_challenge, random_urlsafe_string, store_login_state,
+        store_user_token, take_login_state,
+    },
+    states::GlobalConfig,
+};
+
+#[get("/auth/twitch/login")]
+pub fn twitch_login(
+    state: &State<GlobalConfig>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Status> {
+    let redirect_uri = state.redirect_uri.as_ref().ok_or(Status::NotFound)?;
+
+    let login_state = random_urlsafe_string();
+    let code_verifier = random_urlsafe_string();
+    store_login_state(cookies, &login_state, &code_verifier);
+
+    Ok(Redirect::to(get_authorize_url(
+        &state.client_id,
+        redirect_uri,
+        &state.login_scopes,
+        &login_state,
+        &pkce_challenge(&code_verifier),
+    )))
+}
+
+#[get("/auth/twitch/callback?<code>&<state>&<error>")]
+pub async fn twitch_callback(
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    config: &State<GlobalConfig>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Status> {
+    let redirect_uri = config.redirect_uri.as_ref().ok_or(Status::NotFound)?;
+    let (expected_state, code_verifier) = take_login_state(cookies).ok_or(Status::BadRequest)?;
+
+    if let Some(error) = error {
+        info!(%error, "twitch_callback: login was not authorized");
+        return Err(Status::Unauthorized);
+    }
+
+    if state.as_deref() != Some(expected_state.as_str()) {
+        info!("twitch_callback: state did not match the pending login");
+        return Err(Status::BadRequest);
+    }
+
+    let code = code.ok_or(Status::BadRequest)?;
+    let token = exchange_code(
+        &config.client_id,
+        &config.app_token.client_secret(),
+        &code,
+        redirect_uri,
+        &code_verifier,
+    )
+    .await?;
+
+    store_user_token(cookies, &token);
+
+    Ok(Redirect::to(config.login_redirect.clone()))
+}
+
+#[post("/auth/twitch/logout")]
+pub fn twitch_logout(cookies: &CookieJar<'_>) -> Status {
+    clear_user_token(cookies);
+    Status::NoContent
+}
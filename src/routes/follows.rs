@@ -1,21 +1,31 @@
 use crate::{
     clients::twitch::user::{get_user_follows, TwitchUserFollow},
-    guards::twitch_auth::AccessTokenResponse,
-    routes::streams::STREAMS_CACHE,
-    states::GlobalConfig,
+    guards::{api_key::ApiKey, twitch_auth::AccessTokenResponse},
+    providers::Platform,
+    snapshot::SnapshotStore,
 };
-use rocket::{get, info, serde::json::Json, State};
+use rocket::{get, serde::json::Json, State};
+use tracing::{debug, info};
 
 #[get("/follows")]
 pub async fn get_follows_for_user(
-    state: &State<GlobalConfig>,
+    api_key: ApiKey,
+    snapshots: &State<SnapshotStore>,
     access_token: AccessTokenResponse,
 ) -> Json<Vec<TwitchUserFollow>> {
-    let parsed_token = access_token.token.split(' ').collect::<Vec<&str>>();
+    info!(
+        login = %access_token.validate_token.login,
+        api_key = api_key.name(),
+        "get_follows_for_user: fetching follows"
+    );
+
+    // tokens from an allowed first-party client must be sent with the client id they were
+    // issued to, which the guard has already checked
+    let client_id = &access_token.validate_token.client_id;
 
     let mut all_follows = get_user_follows(
-        &state.client_id,
-        parsed_token[0],
+        client_id,
+        &access_token.token,
         &access_token.validate_token.user_id,
         "",
     )
@@ -23,19 +33,19 @@ pub async fn get_follows_for_user(
     let mut cursor = all_follows.pagination.cursor;
 
     while cursor.is_some() {
-        info!("get_twitch_user_follows: fetching cursor {:?}", cursor);
+        debug!(?cursor, "get_twitch_user_follows: fetching cursor");
 
         let mut stream_response = get_user_follows(
-            &state.client_id,
-            parsed_token[0],
+            client_id,
+            &access_token.token,
             &access_token.validate_token.user_id,
             cursor.unwrap().as_str(),
         )
         .await;
 
-        info!(
-            "get_twitch_user_follows: got {} follows",
-            stream_response.data.len()
+        debug!(
+            follows = stream_response.data.len(),
+            "get_twitch_user_follows: got follows"
         );
 
         all_follows.data.append(&mut stream_response.data);
@@ -45,11 +55,11 @@ pub async fn get_follows_for_user(
 
     let data = all_follows.data;
 
-    let streams = STREAMS_CACHE.lock().unwrap();
+    let snapshot = snapshots.load();
 
     let follows = data
         .into_iter()
-        .filter(|d| streams.iter().any(|s| s.user_id == d.to_id))
+        .filter(|d| snapshot.by_user_id(Platform::Twitch, &d.to_id).is_some())
         .collect();
 
     Json(follows)
@@ -0,0 +1,86 @@
+use rocket::{get, http::Status, State};
+use serde::Serialize;
+
+use crate::{
+    poller::PollerState,
+    snapshot::SnapshotStore,
+    states::{GlobalConfig, TokenFetch},
+    utils::JsonResponse,
+};
+
+#[derive(Debug, Serialize)]
+pub struct HealthStatus {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    failing: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot_version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot_age_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poller: Option<PollerState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poller_restarts: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poller_last_error: Option<String>,
+}
+
+#[get("/health/live")]
+pub fn get_liveness() -> JsonResponse<HealthStatus> {
+    let response = HealthStatus {
+        status: "ok",
+        failing: vec![],
+        snapshot_version: None,
+        snapshot_age_seconds: None,
+        poller: None,
+        poller_restarts: None,
+        poller_last_error: None,
+    };
+
+    JsonResponse::new(response, Status::Ok)
+}
+
+#[get("/health/ready")]
+pub fn get_readiness(
+    state: &State<GlobalConfig>,
+    snapshots: &State<SnapshotStore>,
+) -> JsonResponse<HealthStatus> {
+    let snapshot = snapshots.load();
+    let (is_polled, version, age) = (snapshot.is_polled(), snapshot.version, snapshot.age());
+
+    let mut failing = vec![];
+    if !is_polled {
+        failing.push("streams_not_polled");
+    } else if age > state.max_snapshot_age {
+        failing.push("streams_snapshot_stale");
+    }
+    if state.poller.state() == PollerState::Stopped {
+        failing.push("poller_stopped");
+    }
+    match state.app_token.startup_fetch() {
+        TokenFetch::Pending => failing.push("app_token_pending"),
+        TokenFetch::Rejected => failing.push("app_token_rejected"),
+        TokenFetch::Fetched | TokenFetch::Unreachable => {
+            if state.app_token.is_refresh_failing() {
+                failing.push("app_token_refresh_failing");
+            }
+        }
+    }
+
+    let (status, status_code) = match failing.is_empty() {
+        true => ("ready", Status::Ok),
+        false => ("not_ready", Status::ServiceUnavailable),
+    };
+
+    let response = HealthStatus {
+        status,
+        failing,
+        snapshot_version: Some(version),
+        snapshot_age_seconds: Some(age.as_secs()),
+        poller: Some(state.poller.state()),
+        poller_restarts: Some(state.poller.restarts()),
+        poller_last_error: state.poller.last_error(),
+    };
+
+    JsonResponse::new(response, status_code)
+}
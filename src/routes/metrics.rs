@@ -0,0 +1,8 @@
+use rocket::{get, http::ContentType};
+
+use crate::metrics::render;
+
+#[get("/metrics")]
+pub fn get_metrics() -> (ContentType, String) {
+    (ContentType::Plain, render())
+}
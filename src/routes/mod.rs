@@ -1,3 +1,7 @@
+pub mod admin;
+pub mod auth;
 pub mod follows;
+pub mod health;
+pub mod metrics;
 pub mod stream;
 pub mod streams;
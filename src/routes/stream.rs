@@ -1,55 +1,171 @@
-use futures::future::join;
-use rocket::{get, http::Status, info, State};
+use futures::future::{join, join_all};
+use rocket::{get, http::Status, State};
 use serde::Serialize;
+use tracing::{debug, warn};
 
 use crate::{
-    clients::twitch::{
-        user::{self, TwitchUser},
-        TwitchStream,
-    },
-    states::GlobalConfig,
+    creators::{Creator, CreatorAccount},
+    guards::api_key::ApiKey,
+    providers::{Channel, LiveStream, Platform, ProviderStore, Providers},
+    snapshot::{SnapshotStore, StreamsSnapshot},
     utils::JsonResponse,
 };
 
 #[derive(Debug, Serialize)]
 pub struct StreamDetail {
     #[serde(skip_serializing_if = "Option::is_none")]
-    stream_info: Option<TwitchStream>,
-    user_info: Option<TwitchUser>,
+    stream_info: Option<LiveStream>,
+    user_info: Option<Channel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator: Option<CreatorDetail>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatorDetail {
+    id: String,
+    name: String,
+    /// The creator's channels other than the one in `user_info`.
+    other_channels: Vec<CreatorChannel>,
 }
 
-#[get("/stream/<username>")]
+#[derive(Debug, Serialize)]
+pub struct CreatorChannel {
+    #[serde(flatten)]
+    account: CreatorAccount,
+    url: String,
+}
+
+/// `username` can also be any of a registered creator's logins, or `creator:<id>`, in which case
+/// the creator's live channel is shown, falling back to the one asked for.
+#[get("/stream/<username>?<platform>")]
 pub async fn get_stream(
     username: String,
-    state: &State<GlobalConfig>,
+    platform: Option<Platform>,
+    api_key: ApiKey,
+    providers: &State<ProviderStore>,
+    snapshots: &State<SnapshotStore>,
 ) -> Result<JsonResponse<StreamDetail>, Status> {
-    let token = state.fetch_access_token();
+    let platform = platform.unwrap_or_default();
+    debug!(%username, ?platform, api_key = api_key.name(), "get_stream");
+
+    let snapshot = snapshots.load();
+    let providers = providers.load();
+
+    if let Some(creator) = snapshot.creators().resolve(platform, &username) {
+        let response =
+            get_creator_stream(creator, platform, &username, &providers, &snapshot).await?;
+        return Ok(JsonResponse::new(response, Status::Ok));
+    }
 
-    let twitch_user = user::get_user(&state.client_id, &token, &username);
-    let twitch_stream = user::get_stream(&state.client_id, &token, &username);
+    let provider = providers.get(platform).ok_or(Status::NotFound)?;
 
-    let (user, stream) = join(twitch_user, twitch_stream).await;
+    // streams in the snapshot are at most one poll old, so only ask the platform about the rest
+    let live_stream = snapshot.by_login(platform, &username).cloned();
 
-    let mut user_data = user?.data;
-    info!("user_data got {:?}", user_data);
+    let channel = provider.channel(&username);
 
-    let user_info = match user_data.len() {
-        1 => Some(user_data.swap_remove(0)),
-        _ => return Err(Status::NotFound),
+    let (user, stream) = match live_stream {
+        Some(_) => (channel.await, None),
+        None => {
+            let (user, stream) = join(channel, provider.channel_stream(&username)).await;
+            (user, Some(stream))
+        }
     };
 
-    let mut stream_user_data = stream?.data;
-    info!("stream_user got {:?}", stream_user_data);
+    let user_info = Some(user?);
 
-    let stream_info = match stream_user_data.len() {
-        1 => Some(stream_user_data.swap_remove(0)),
-        _ => None,
+    let stream_info = match stream {
+        Some(stream) => stream?,
+        None => live_stream,
     };
 
     let response = StreamDetail {
         stream_info,
         user_info,
+        creator: None,
     };
 
     Ok(JsonResponse::new(response, Status::Ok))
 }
+
+/// Finds whichever of the creator's accounts is live, checking the snapshot before asking the
+/// platforms.
+async fn live_account<'a>(
+    creator: &'a Creator,
+    providers: &Providers,
+    snapshot: &StreamsSnapshot,
+) -> Option<(&'a CreatorAccount, LiveStream)> {
+    let cached = creator.accounts.iter().find_map(|account| {
+        snapshot
+            .by_login(account.platform, &account.login)
+            .map(|stream| (account, stream.clone()))
+    });
+    if cached.is_some() {
+        return cached;
+    }
+
+    let lookups = creator.accounts.iter().filter_map(|account| {
+        let provider = providers.get(account.platform)?;
+        Some(async move { (account, provider.channel_stream(&account.login).await) })
+    });
+
+    join_all(lookups)
+        .await
+        .into_iter()
+        .find_map(|(account, stream)| match stream {
+            Ok(stream) => stream.map(|stream| (account, stream)),
+            Err(status) => {
+                warn!(
+                    creator = %creator.id,
+                    platform = %account.platform,
+                    %status,
+                    "get_stream: failed to look up creator stream"
+                );
+                None
+            }
+        })
+}
+
+async fn get_creator_stream(
+    creator: &Creator,
+    platform: Platform,
+    username: &str,
+    providers: &Providers,
+    snapshot: &StreamsSnapshot,
+) -> Result<StreamDetail, Status> {
+    let (account, stream_info) = match live_account(creator, providers, snapshot).await {
+        Some((account, stream)) => (account, Some(stream)),
+        None => {
+            let requested = creator.accounts.iter().find(|account| {
+                account.platform == platform && account.login.eq_ignore_ascii_case(username)
+            });
+            let account = requested
+                .or_else(|| creator.accounts.first())
+                .ok_or(Status::NotFound)?;
+            (account, None)
+        }
+    };
+
+    let provider = providers.get(account.platform).ok_or(Status::NotFound)?;
+    let user_info = Some(provider.channel(&account.login).await?);
+
+    let other_channels = creator
+        .accounts
+        .iter()
+        .filter(|other| !std::ptr::eq(*other, account))
+        .map(|other| CreatorChannel {
+            account: other.clone(),
+            url: other.url(),
+        })
+        .collect();
+
+    Ok(StreamDetail {
+        stream_info,
+        user_info,
+        creator: Some(CreatorDetail {
+            id: creator.id.clone(),
+            name: creator.name.clone(),
+            other_channels,
+        }),
+    })
+}
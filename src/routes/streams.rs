@@ -1,128 +1,218 @@
-use crate::{category::Category, clients::twitch::{Token, TwitchStream, TwitchStreamsResponse, get_science_and_tech_streams, get_software_game_dev_streams, get_token}, states::GlobalConfig, utils::{filter_all_programming_streams, filter_by_category, JsonResponse}};
+use crate::{
+    category::Category,
+    fairings::compression::COMPRESSION_CACHE,
+    guards::api_key::ApiKey,
+    metrics::{
+        unix_now, LAST_SUCCESSFUL_POLL, LISTED_STREAMS, LIVE_STREAMS, POLL_FAILURES, POLL_INTERVAL,
+        POLL_PAGES, POLL_TIMEOUTS,
+    },
+    poller::PollControl,
+    providers::{LiveStream, ProviderStore, Providers},
+    snapshot::{CrawledStream, SnapshotStore, StreamsSnapshot},
+    utils::JsonResponse,
+};
 
-use once_cell::sync::Lazy;
+use futures::future::join_all;
 use rocket::{
     get,
     http::Status,
-    info,
+    serde::json::serde_json,
+    tokio::{task, time},
     State,
-    tokio::{join, time::Interval}
 };
-use std::sync::Mutex;
-
-pub static STREAMS_CACHE: Lazy<Mutex<Vec<TwitchStream>>> = Lazy::new(|| Mutex::new(vec![]));
-
-pub fn fetch_access_token(token: Token, client_id: &str, client_secret: &str) -> Token {
-    let expired = std::time::Duration::from_secs(token.expires_in);
-    let expiring_time = std::time::Instant::now() + expired;
-    let is_expired = std::time::Instant::now() >= expiring_time;
+use std::{sync::Arc, time::Instant};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+fn record_live_streams(snapshot: &StreamsSnapshot) {
+    LISTED_STREAMS.reset();
+    LISTED_STREAMS
+        .with_label_values(&["all"])
+        .set(snapshot.listing_len(&None) as i64);
+    for category in snapshot.listing_categories() {
+        LISTED_STREAMS
+            .with_label_values(&[&format!("{:?}", category)])
+            .set(snapshot.listing_len(&Some(category.clone())) as i64);
+    }
 
-    if is_expired {
-        info!("token expired at: {:?}", std::time::Instant::now());
-        get_token(client_id, client_secret)
-    } else {
-        token
+    LIVE_STREAMS.reset();
+    for (platform, category_id) in snapshot.categories() {
+        let mut streams = snapshot.in_category(platform, category_id).peekable();
+        if let Some(game_name) = streams.peek().map(|s| s.game_name.clone()) {
+            LIVE_STREAMS
+                .with_label_values(&[platform.name(), &game_name])
+                .set(streams.count() as i64);
+        }
     }
 }
 
-pub enum TwitchCategory {
-    ScienceAndTechnology,
-    SoftwareAndGameDevelopment,
+/// Crawls every source of every provider concurrently, returning the streams and pages fetched,
+/// and whether every source failed.
+async fn crawl_streams(providers: &Providers, max_pages: u64) -> (Vec<CrawledStream>, u64, bool) {
+    let crawls = providers.iter().flat_map(|provider| {
+        provider.sources().iter().map(move |source| async move {
+            let crawl = provider.live_streams(source, max_pages).await;
+            POLL_PAGES
+                .with_label_values(&[provider.platform().name(), &source.name])
+                .set(crawl.pages as i64);
+            crawl
+        })
+    });
+
+    let crawls = join_all(crawls).await;
+    let all_failed = !crawls.is_empty() && crawls.iter().all(|crawl| crawl.failed);
+
+    let (streams, pages) =
+        crawls
+            .into_iter()
+            .fold((vec![], 0), |(mut streams, pages), mut crawl| {
+                streams.append(&mut crawl.streams);
+                (streams, pages + crawl.pages)
+            });
+    (streams, pages, all_failed)
 }
 
-pub async fn fetch_all_livestreams(
-    mut all_streams: TwitchStreamsResponse,
-    client_id: &str,
-    access_token: &str,
-    stream_source: TwitchCategory,
-) -> TwitchStreamsResponse {
-    let mut cursor = all_streams.pagination.cursor.clone();
-
-    info!(
-        "fetch_all_livestreams: starting total {}",
-        all_streams.data.len()
-    );
-    while cursor.is_some() {
-        info!("fetch_all_livestreams: fetching cursor {:?}", cursor);
+pub async fn fetch_streams_interval(
+    store: SnapshotStore,
+    control: Arc<PollControl>,
+    providers: ProviderStore,
+) {
+    // carry on from the snapshot left by a previous run of the poller
+    let mut version = store.load().version;
+    let mut interval = control.config().min_interval();
 
-        let mut stream_response = match stream_source {
-            TwitchCategory::ScienceAndTechnology => {
-                get_science_and_tech_streams(client_id, access_token, cursor.unwrap().as_str())
-                    .await
+    loop {
+        let started_at = Instant::now();
+        let poll = control.config();
+        let providers = providers.load();
+
+        let span = info_span!("poll_cycle", cycle = version + 1);
+        // a crawl may not outlast the interval, so cycles never pile up behind a slow one
+        let crawl = time::timeout(interval, crawl_streams(&providers, poll.max_pages))
+            .instrument(span.clone())
+            .await;
+
+        let (data, pages) = match crawl {
+            Ok((data, pages, false)) => (data, pages),
+            // an empty crawl would let the grace period empty the listing, and readiness
+            // should see the snapshot go stale instead
+            Ok((_, _, true)) => {
+                POLL_FAILURES.inc();
+                interval = poll.backoff_interval(interval);
+                POLL_INTERVAL.set(interval.as_secs() as i64);
+                span.in_scope(|| {
+                    warn!(
+                        next_interval_secs = interval.as_secs(),
+                        "every source failed, keeping the previous snapshot"
+                    )
+                });
+                control.wait_until(started_at + interval).await;
+                continue;
             }
-            TwitchCategory::SoftwareAndGameDevelopment => {
-                get_software_game_dev_streams(client_id, access_token, cursor.unwrap().as_str())
-                    .await
+            Err(_) => {
+                POLL_TIMEOUTS.inc();
+                interval = poll.backoff_interval(interval);
+                POLL_INTERVAL.set(interval.as_secs() as i64);
+                span.in_scope(|| {
+                    warn!(
+                        next_interval_secs = interval.as_secs(),
+                        "poll cycle overran its interval, keeping the previous snapshot"
+                    )
+                });
+                control.wait_until(started_at + interval).await;
+                continue;
             }
         };
 
-        all_streams.data.append(&mut stream_response.data);
+        let crawl_duration = started_at.elapsed();
+        interval = poll.next_interval(crawl_duration, pages);
+        POLL_INTERVAL.set(interval.as_secs() as i64);
 
-        info!(
-            "fetch_all_livestreams: got {} streams",
-            all_streams.data.len()
-        );
+        version += 1;
+        let next_refresh_at = started_at + interval;
+        let crawled = data.len();
 
-        cursor = stream_response.pagination.cursor;
-    }
+        let snapshot = store
+            .update(|current| current.merge(version, data, next_refresh_at, poll.grace_period()));
 
-    all_streams
-}
+        span.in_scope(|| {
+            info!(
+                crawled,
+                streams = snapshot.streams.len(),
+                pages,
+                duration_ms = crawl_duration.as_millis() as u64,
+                next_interval_secs = interval.as_secs(),
+                "poll cycle finished"
+            )
+        });
 
-pub async fn fetch_streams_interval(
-    mut interval: Interval,
-    client_id: String,
-    client_secret: String,
-    token: Token,
-) -> () {
-    loop {
-        interval.tick().await;
+        record_live_streams(&snapshot);
 
-        let access_token =
-            fetch_access_token(token.clone(), &client_id, &client_secret).access_token;
+        let default_etag = listing_etag(&snapshot.etag, &None, &None, false);
+        let default_listing = snapshot.listing(&None, &None);
 
-        let science_and_tech_stream_handle = fetch_all_livestreams(
-            get_science_and_tech_streams(&client_id, &access_token, "").await,
-            &client_id,
-            &access_token,
-            TwitchCategory::ScienceAndTechnology,
-        );
+        LAST_SUCCESSFUL_POLL.set(unix_now());
 
-        let software_and_game_dev_streams_handle = fetch_all_livestreams(
-            get_software_game_dev_streams(&client_id, &access_token, "").await,
-            &client_id,
-            &access_token,
-            TwitchCategory::SoftwareAndGameDevelopment,
-        );
+        // compress the listing most clients poll once, off the async workers
+        if let Ok(body) = serde_json::to_vec(&default_listing) {
+            let _ =
+                task::spawn_blocking(move || COMPRESSION_CACHE.replace(&default_etag, &body)).await;
+        }
 
-        let (science_and_tech_streams, mut software_and_game_dev_streams) = join!(
-            science_and_tech_stream_handle,
-            software_and_game_dev_streams_handle
-        );
-
-        let mut data = science_and_tech_streams.data;
+        control.wait_until(next_refresh_at).await;
+    }
+}
 
-        data.append(&mut software_and_game_dev_streams.data);
+fn listing_etag(
+    snapshot_etag: &str,
+    category: &Option<Category>,
+    language: &Option<String>,
+    group: bool,
+) -> String {
+    let etag = match category {
+        Some(c) => format!("{}-{:?}", snapshot_etag, c),
+        None => format!("{}-all", snapshot_etag),
+    };
 
-        data.sort_by(|a, b| b.viewer_count.cmp(&a.viewer_count));
+    let etag = match language {
+        Some(language) => format!("{}-{}", etag, language),
+        None => etag,
+    };
 
-        *STREAMS_CACHE.lock().unwrap() = data;
+    match group {
+        true => format!("{}-grouped", etag),
+        false => etag,
     }
 }
 
-#[get("/streams?<category>")]
+/// `group` folds each registered creator's simulcasts into one card.
+#[get("/streams?<category>&<language>&<group>")]
 pub async fn get_streams(
-    state: &State<GlobalConfig>,
+    api_key: ApiKey,
+    snapshots: &State<SnapshotStore>,
     category: Option<Category>,
-) -> JsonResponse<Vec<TwitchStream>> {
-    let data = STREAMS_CACHE.lock().unwrap().clone();
+    language: Option<String>,
+    group: Option<bool>,
+) -> JsonResponse<Vec<LiveStream>> {
+    let snapshot = snapshots.load();
+
+    let group = group.unwrap_or(false);
+
+    debug!(
+        ?category,
+        ?language,
+        group,
+        api_key = api_key.name(),
+        "get_streams"
+    );
 
-    info!("got category {:?}", category);
+    let etag = listing_etag(&snapshot.etag, &category, &language, group);
 
-    let streams = match category {
-        Some(c) => filter_by_category(data, state.tags.get(&c).unwrap(), &state.all_tags),
-        None => filter_all_programming_streams(data, &state.tags, &state.all_tags),
+    let streams = match group {
+        true => snapshot.grouped_listing(&category, &language),
+        false => snapshot.listing(&category, &language),
     };
 
     JsonResponse::new(streams, Status::Ok)
+        .with_etag(etag)
+        .with_max_age(snapshot.max_age().as_secs())
 }
@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    clients::twitch::{refresh_user_token, UserToken},
+    guards::twitch_auth::AccessTokenError,
+    states::GlobalConfig,
+};
+
+pub const ACCESS_TOKEN_COOKIE: &str = "twitch_access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "twitch_refresh_token";
+pub const EXPIRES_AT_COOKIE: &str = "twitch_token_expires_at";
+pub const LOGIN_STATE_COOKIE: &str = "twitch_login_state";
+
+// refresh a little early so the token doesn't expire between validation and use
+const REFRESH_MARGIN_SECS: u64 = 60;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn random_urlsafe_string() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+fn session_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    Cookie::build(name, value)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .finish()
+}
+
+/// Stores the state and PKCE verifier of a login until Twitch redirects back.
+pub fn store_login_state(jar: &CookieJar<'_>, state: &str, code_verifier: &str) {
+    let mut cookie = session_cookie(LOGIN_STATE_COOKIE, format!("{}.{}", state, code_verifier));
+    cookie.set_max_age(rocket::time::Duration::minutes(10));
+    jar.add_private(cookie);
+}
+
+/// Returns the `(state, code_verifier)` of a pending login and forgets it.
+pub fn take_login_state(jar: &CookieJar<'_>) -> Option<(String, String)> {
+    let cookie = jar.get_private(LOGIN_STATE_COOKIE)?;
+    jar.remove_private(Cookie::named(LOGIN_STATE_COOKIE));
+
+    let (state, code_verifier) = cookie.value().split_once('.')?;
+    Some((state.to_owned(), code_verifier.to_owned()))
+}
+
+pub fn store_user_token(jar: &CookieJar<'_>, token: &UserToken) {
+    let expires_at = now_secs() + token.expires_in;
+
+    jar.add_private(session_cookie(
+        ACCESS_TOKEN_COOKIE,
+        token.access_token.clone(),
+    ));
+    jar.add_private(session_cookie(
+        REFRESH_TOKEN_COOKIE,
+        token.refresh_token.clone(),
+    ));
+    jar.add_private(session_cookie(EXPIRES_AT_COOKIE, expires_at.to_string()));
+}
+
+pub fn clear_user_token(jar: &CookieJar<'_>) {
+    for name in [ACCESS_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE, EXPIRES_AT_COOKIE] {
+        jar.remove_private(Cookie::named(name));
+    }
+}
+
+/// Reads the user's access token from the session cookies, refreshing it
+/// with the stored refresh token once it is about to expire.
+pub async fn session_access_token(
+    jar: &CookieJar<'_>,
+    config: &GlobalConfig,
+) -> Result<String, AccessTokenError> {
+    let access_token = jar
+        .get_private(ACCESS_TOKEN_COOKIE)
+        .ok_or(AccessTokenError::Missing)?;
+    let expires_at = jar
+        .get_private(EXPIRES_AT_COOKIE)
+        .and_then(|c| c.value().parse::<u64>().ok())
+        .unwrap_or(0);
+
+    if now_secs() + REFRESH_MARGIN_SECS < expires_at {
+        return Ok(access_token.value().to_owned());
+    }
+
+    let refresh_token = jar
+        .get_private(REFRESH_TOKEN_COOKIE)
+        .ok_or(AccessTokenError::Expired)?;
+
+    match refresh_user_token(
+        &config.client_id,
+        &config.app_token.client_secret(),
+        refresh_token.value(),
+    )
+    .await
+    {
+        Ok(token) => {
+            store_user_token(jar, &token);
+            Ok(token.access_token)
+        }
+        Err(status) if status == Status::Unauthorized => {
+            clear_user_token(jar);
+            Err(AccessTokenError::Expired)
+        }
+        Err(_) => Err(AccessTokenError::Unavailable),
+    }
+}
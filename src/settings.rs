@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use arc_swap::ArcSwap;
+use rocket::{
+    figment::{
+        providers::{Format, Toml},
+        Figment,
+    },
+    tokio::time,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    category::{get_twitch_tag_ids, Category},
+    metrics::SETTINGS_RELOADS,
+    poller::{PollConfig, PollControl},
+    providers::{
+        default_twitch_sources, KickConfig, KickProvider, ProviderStore, Providers, Source,
+        TwitchProvider, YoutubeConfig, YoutubeProvider,
+    },
+    snapshot::SnapshotStore,
+    states::AppToken,
+    utils::ListingRules,
+};
+
+fn default_programming_game_ids() -> Vec<String> {
+    // Software and Game Development
+    vec!["1469308723".to_owned()]
+}
+
+fn default_title_blocklist() -> Vec<String> {
+    vec![
+        "minecraft".to_owned(),
+        "fortnite".to_owned(),
+        "pokemon".to_owned(),
+    ]
+}
+
+/// The settings that can change while the service runs. They're read from the Rocket config,
+/// with the file at `settings_path`, when set, layered on top.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Settings {
+    #[serde(default)]
+    pub poll: PollConfig,
+    /// The tag id each listing category matches.
+    #[serde(default = "get_twitch_tag_ids")]
+    pub categories: HashMap<Category, String>,
+    #[serde(default = "default_programming_game_ids")]
+    pub programming_game_ids: Vec<String>,
+    #[serde(default = "default_title_blocklist")]
+    pub title_blocklist: Vec<String>,
+    #[serde(default = "default_twitch_sources")]
+    pub twitch_sources: Vec<Source>,
+    #[serde(default)]
+    pub twitch_tracked_broadcasters: Vec<String>,
+    pub youtube: Option<YoutubeConfig>,
+    pub kick: Option<KickConfig>,
+}
+
+impl Settings {
+    pub fn load(figment: &Figment, path: Option<&Path>) -> Result<Self, String> {
+        let figment = match path {
+            // file values go in the selected profile, so they win over the config's own
+            Some(path) => figment
+                .clone()
+                .merge(Toml::file(path).profile(figment.profile().clone())),
+            None => figment.clone(),
+        };
+
+        let mut settings: Settings = figment.extract().map_err(|e| e.to_string())?;
+        settings.title_blocklist = settings
+            .title_blocklist
+            .iter()
+            .map(|word| word.to_lowercase())
+            .collect();
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.poll.validate()?;
+
+        if self.programming_game_ids.is_empty() {
+            return Err("programming_game_ids is empty".to_owned());
+        }
+        if self.title_blocklist.iter().any(|word| word.is_empty()) {
+            return Err("title_blocklist has an empty entry".to_owned());
+        }
+
+        let mut source_ids = HashSet::new();
+        for source in &self.twitch_sources {
+            if source.id.is_empty() || source.name.is_empty() {
+                return Err("twitch_sources entries need an id and a name".to_owned());
+            }
+            if !source_ids.insert(&source.id) {
+                return Err(format!("twitch source {:?} is listed twice", source.id));
+            }
+        }
+
+        // Helix takes user ids here, a login would quietly match nobody
+        let not_numeric = |id: &&String| id.is_empty() || !id.chars().all(|c| c.is_ascii_digit());
+        if let Some(id) = self.twitch_tracked_broadcasters.iter().find(not_numeric) {
+            return Err(format!(
+                "twitch_tracked_broadcasters entry {:?} isn't a numeric user id",
+                id
+            ));
+        }
+
+        if let Some(youtube) = &self.youtube {
+            youtube.validate()?;
+        }
+        if let Some(kick) = &self.kick {
+            kick.validate()?;
+        }
+        Ok(())
+    }
+
+    fn providers_changed(&self, other: &Settings) -> bool {
+        self.twitch_sources != other.twitch_sources
+            || self.twitch_tracked_broadcasters != other.twitch_tracked_broadcasters
+            || self.youtube != other.youtube
+            || self.kick != other.kick
+    }
+}
+
+/// The settings in use, replaced when the settings file changes them.
+#[derive(Clone)]
+pub struct SettingsStore(Arc<ArcSwap<Settings>>);
+
+impl SettingsStore {
+    pub fn new(settings: Settings) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(settings)))
+    }
+
+    pub fn load(&self) -> Arc<Settings> {
+        self.0.load_full()
+    }
+
+    fn store(&self, settings: Settings) {
+        self.0.store(Arc::new(settings));
+    }
+}
+
+/// Builds the providers for the settings, carrying over the ones whose settings are unchanged
+/// so their tokens and caches survive a reload.
+pub struct ProviderBuilder {
+    client_id: String,
+    app_token: Arc<AppToken>,
+    youtube: Option<(YoutubeConfig, Arc<YoutubeProvider>)>,
+    kick: Option<(KickConfig, Arc<KickProvider>)>,
+}
+
+impl ProviderBuilder {
+    pub fn new(client_id: String, app_token: Arc<AppToken>) -> Self {
+        Self {
+            client_id,
+            app_token,
+            youtube: None,
+            kick: None,
+        }
+    }
+
+    pub fn build(&mut self, settings: &Settings) -> Providers {
+        let mut providers = Providers::default().with(Arc::new(TwitchProvider::new(
+            self.client_id.clone(),
+            self.app_token.clone(),
+            settings.twitch_sources.clone(),
+            settings.twitch_tracked_broadcasters.clone(),
+        )));
+
+        self.youtube = match (&settings.youtube, self.youtube.take()) {
+            (None, _) => None,
+            (Some(config), Some((current, provider))) if *config == current => {
+                Some((current, provider))
+            }
+            // keeps the quota already spent today
+            (Some(config), Some((_, provider))) => Some((
+                config.clone(),
+                Arc::new(provider.reconfigure(config.clone())),
+            )),
+            (Some(config), None) => Some((
+                config.clone(),
+                Arc::new(YoutubeProvider::new(config.clone())),
+            )),
+        };
+
+        self.kick = match (&settings.kick, self.kick.take()) {
+            (None, _) => None,
+            (Some(config), Some((current, provider))) if *config == current => {
+                Some((current, provider))
+            }
+            (Some(config), _) => {
+                Some((config.clone(), Arc::new(KickProvider::new(config.clone()))))
+            }
+        };
+
+        if let Some((_, youtube)) = &self.youtube {
+            providers = providers.with(youtube.clone());
+        }
+        if let Some((_, kick)) = &self.kick {
+            providers = providers.with(kick.clone());
+        }
+        providers
+    }
+}
+
+/// Applies edits to the settings file while the service runs.
+///
+/// Only the sections an edit changes are applied, so settings changed through the admin routes
+/// stay until the file changes them too. Edits that fail to parse or validate are logged and
+/// ignored.
+pub struct SettingsWatcher {
+    pub figment: Figment,
+    pub path: PathBuf,
+    pub interval: Duration,
+    pub settings: SettingsStore,
+    pub provider_builder: ProviderBuilder,
+    pub poll_control: Arc<PollControl>,
+    pub snapshots: SnapshotStore,
+    pub providers: ProviderStore,
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl SettingsWatcher {
+    pub async fn run(mut self) {
+        let mut modified = modified_at(&self.path);
+
+        loop {
+            time::sleep(self.interval).await;
+
+            let latest = modified_at(&self.path);
+            if latest == modified {
+                continue;
+            }
+            modified = latest;
+
+            match Settings::load(&self.figment, Some(&self.path)) {
+                Ok(settings) => self.apply(settings),
+                Err(e) => {
+                    SETTINGS_RELOADS.with_label_values(&["rejected"]).inc();
+                    warn!(
+                        path = %self.path.display(),
+                        error = %e,
+                        "rejected settings file change, keeping the current settings"
+                    );
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, settings: Settings) {
+        let current = self.settings.load();
+        let mut changed = Vec::new();
+
+        if settings.poll != current.poll {
+            self.poll_control.set_config(settings.poll.clone());
+            changed.push("poll");
+        }
+
+        let rules_changed = settings.categories != current.categories
+            || settings.programming_game_ids != current.programming_game_ids
+            || settings.title_blocklist != current.title_blocklist;
+        if rules_changed {
+            self.snapshots.update(|snapshot| {
+                let mut rules: ListingRules = snapshot.rules().clone();
+                if settings.categories != current.categories {
+                    rules.tags = settings.categories.clone();
+                }
+                if settings.programming_game_ids != current.programming_game_ids {
+                    rules.programming_game_ids = settings.programming_game_ids.clone();
+                }
+                if settings.title_blocklist != current.title_blocklist {
+                    rules.title_blocklist = settings.title_blocklist.clone();
+                }
+                snapshot.with_rules(Arc::new(rules))
+            });
+            changed.push("listing rules");
+        }
+
+        if settings.providers_changed(&current) {
+            self.providers.store(self.provider_builder.build(&settings));
+            changed.push("providers");
+        }
+
+        if changed.is_empty() {
+            return;
+        }
+
+        SETTINGS_RELOADS.with_label_values(&["applied"]).inc();
+        info!(path = %self.path.display(), ?changed, "applied settings file change");
+        self.settings.store(settings);
+    }
+}
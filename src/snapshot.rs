@@ -0,0 +1,576 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use arc_swap::ArcSwap;
+use rocket::serde::json::serde_json;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    category::Category,
+    creators::CreatorRegistry,
+    metrics::unix_now,
+    providers::{Inclusion, LiveStream, Platform},
+    utils::ListingRules,
+};
+
+/// Identifies something on a platform; ids are only unique within one.
+type PlatformKey = (Platform, String);
+
+impl LiveStream {
+    fn key(&self) -> PlatformKey {
+        (self.platform, self.id.clone())
+    }
+}
+
+/// On-disk form of a snapshot, written on shutdown and read back at startup.
+#[derive(Serialize, Deserialize)]
+struct PersistedSnapshot {
+    version: u64,
+    /// Unix time the snapshot was refreshed at.
+    refreshed_at: i64,
+    streams: Vec<LiveStream>,
+}
+
+/// A stream returned by a crawl, with when the page holding it was fetched.
+pub struct CrawledStream {
+    pub stream: LiveStream,
+    pub seen_at: Instant,
+}
+
+/// Positions in [`StreamsSnapshot::streams`], keyed by the fields streams are looked up by.
+#[derive(Default)]
+struct StreamIndexes {
+    by_id: HashMap<PlatformKey, usize>,
+    by_user_id: HashMap<PlatformKey, usize>,
+    /// Keyed by the lowercased login.
+    by_login: HashMap<PlatformKey, usize>,
+    /// Keyed by the platform's category (Twitch game) id.
+    by_category: HashMap<PlatformKey, Vec<usize>>,
+}
+
+impl StreamIndexes {
+    fn new(streams: &[LiveStream]) -> Self {
+        let mut indexes = Self::default();
+
+        for (i, stream) in streams.iter().enumerate() {
+            indexes.by_id.insert(stream.key(), i);
+            indexes
+                .by_user_id
+                .insert((stream.platform, stream.user_id.clone()), i);
+            indexes
+                .by_login
+                .insert((stream.platform, stream.user_login.to_lowercase()), i);
+            indexes
+                .by_category
+                .entry((stream.platform, stream.game_id.clone()))
+                .or_default()
+                .push(i);
+        }
+
+        indexes
+    }
+}
+
+/// Identifies a listing: a category (`None` for the default listing) and an optional language.
+type ViewKey = (Option<Category>, Option<String>);
+
+/// The listings served by `/streams`, worked out once when the snapshot is built.
+struct ListingViews {
+    /// Listed streams with tag names instead of ids; blacklisted streams are left out.
+    streams: Vec<LiveStream>,
+    /// Positions in `streams` for each listing, by viewer count.
+    views: HashMap<ViewKey, Vec<usize>>,
+}
+
+impl ListingViews {
+    fn new(streams: &[LiveStream], rules: &ListingRules) -> Self {
+        let listed: Vec<LiveStream> = streams
+            .iter()
+            .filter(|stream| !rules.is_blacklisted(stream) && !rules.is_denied(stream))
+            .map(|stream| rules.with_creator(rules.with_tag_names(stream.clone())))
+            .collect();
+
+        let mut views: HashMap<ViewKey, Vec<usize>> = HashMap::new();
+        for (i, stream) in listed.iter().enumerate() {
+            let mut categories: Vec<Option<Category>> = rules
+                .tags
+                .keys()
+                .filter(|category| rules.matches_category(stream, category))
+                .cloned()
+                .map(Some)
+                .collect();
+            if rules.is_programming(stream) {
+                categories.push(None);
+            }
+
+            for category in categories {
+                views.entry((category.clone(), None)).or_default().push(i);
+                views
+                    .entry((category, Some(stream.language.clone())))
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        Self {
+            streams: listed,
+            views,
+        }
+    }
+}
+
+/// The streams from one completed poll. Never mutated once built; see [`SnapshotStore`].
+pub struct StreamsSnapshot {
+    pub version: u64,
+    /// Strong validator derived from the snapshot contents.
+    pub etag: String,
+    pub streams: Vec<LiveStream>,
+    indexes: StreamIndexes,
+    listings: ListingViews,
+    rules: Arc<ListingRules>,
+    /// When each stream, by id, last appeared in a crawl.
+    last_seen: HashMap<PlatformKey, Instant>,
+    pub refreshed_at: Instant,
+    pub next_refresh_at: Instant,
+}
+
+impl StreamsSnapshot {
+    pub fn new(
+        version: u64,
+        streams: Vec<LiveStream>,
+        next_refresh_at: Instant,
+        rules: Arc<ListingRules>,
+    ) -> Self {
+        let serialized = serde_json::to_vec(&streams).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        hasher.update(&serialized);
+        hasher.update(rules.fingerprint().as_bytes());
+        let digest = hasher.finalize();
+        let etag = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+        let refreshed_at = Instant::now();
+        let last_seen = streams
+            .iter()
+            .map(|stream| (stream.key(), refreshed_at))
+            .collect();
+
+        Self {
+            version,
+            etag,
+            indexes: StreamIndexes::new(&streams),
+            listings: ListingViews::new(&streams, &rules),
+            rules,
+            streams,
+            last_seen,
+            refreshed_at,
+            next_refresh_at,
+        }
+    }
+
+    pub fn get(&self, platform: Platform, id: &str) -> Option<&LiveStream> {
+        self.indexes
+            .by_id
+            .get(&(platform, id.to_owned()))
+            .map(|&i| &self.streams[i])
+    }
+
+    pub fn by_user_id(&self, platform: Platform, user_id: &str) -> Option<&LiveStream> {
+        self.indexes
+            .by_user_id
+            .get(&(platform, user_id.to_owned()))
+            .map(|&i| &self.streams[i])
+    }
+
+    pub fn by_login(&self, platform: Platform, login: &str) -> Option<&LiveStream> {
+        self.indexes
+            .by_login
+            .get(&(platform, login.to_lowercase()))
+            .map(|&i| &self.streams[i])
+    }
+
+    /// Streams in one of a platform's categories, by viewer count.
+    pub fn in_category<'a>(
+        &'a self,
+        platform: Platform,
+        category_id: &str,
+    ) -> impl Iterator<Item = &'a LiveStream> {
+        self.positions(
+            self.indexes
+                .by_category
+                .get(&(platform, category_id.to_owned())),
+        )
+    }
+
+    /// The `/streams` listing for a category and language, by viewer count.
+    pub fn listing(
+        &self,
+        category: &Option<Category>,
+        language: &Option<String>,
+    ) -> Vec<LiveStream> {
+        self.listings
+            .views
+            .get(&(category.clone(), language.clone()))
+            .into_iter()
+            .flatten()
+            .map(|&i| self.listings.streams[i].clone())
+            .collect()
+    }
+
+    /// Like [`listing`](Self::listing), but each creator's streams are folded into their most
+    /// watched one, under `simulcasts`.
+    pub fn grouped_listing(
+        &self,
+        category: &Option<Category>,
+        language: &Option<String>,
+    ) -> Vec<LiveStream> {
+        let mut grouped: Vec<LiveStream> = vec![];
+        let mut cards: HashMap<&str, usize> = HashMap::new();
+
+        let positions = self
+            .listings
+            .views
+            .get(&(category.clone(), language.clone()))
+            .into_iter()
+            .flatten();
+        for &i in positions {
+            let stream = &self.listings.streams[i];
+            match stream.creator_id.as_deref() {
+                Some(creator_id) => match cards.get(creator_id) {
+                    Some(&card) => grouped[card].simulcasts.push(stream.clone()),
+                    None => {
+                        cards.insert(creator_id, grouped.len());
+                        grouped.push(stream.clone());
+                    }
+                },
+                None => grouped.push(stream.clone()),
+            }
+        }
+
+        grouped
+    }
+
+    pub fn rules(&self) -> &ListingRules {
+        &self.rules
+    }
+
+    pub fn creators(&self) -> &CreatorRegistry {
+        &self.rules.creators
+    }
+
+    /// Categories that listings are worked out for.
+    pub fn listing_categories(&self) -> impl Iterator<Item = &Category> {
+        self.rules.tags.keys()
+    }
+
+    /// Number of streams in the listing for a category, across languages.
+    pub fn listing_len(&self, category: &Option<Category>) -> usize {
+        self.listings
+            .views
+            .get(&(category.clone(), None))
+            .map_or(0, |positions| positions.len())
+    }
+
+    /// The platform categories (Twitch games) that have live streams.
+    pub fn categories(&self) -> impl Iterator<Item = (Platform, &str)> {
+        self.indexes
+            .by_category
+            .keys()
+            .map(|(platform, id)| (*platform, id.as_str()))
+    }
+
+    fn positions<'a>(
+        &'a self,
+        positions: Option<&'a Vec<usize>>,
+    ) -> impl Iterator<Item = &'a LiveStream> {
+        positions
+            .into_iter()
+            .flatten()
+            .map(move |&i| &self.streams[i])
+    }
+
+    /// Builds the snapshot that follows this one from a crawl.
+    ///
+    /// Pagination over a changing list can return a stream twice, so only the most recently
+    /// fetched record of each id is kept. Streams this snapshot had that the crawl missed are
+    /// carried forward until they have gone unseen for longer than `grace_period`.
+    pub fn merge(
+        &self,
+        version: u64,
+        crawled: Vec<CrawledStream>,
+        next_refresh_at: Instant,
+        grace_period: Duration,
+    ) -> Self {
+        let mut fresh: HashMap<PlatformKey, CrawledStream> = HashMap::with_capacity(crawled.len());
+        for crawled_stream in crawled {
+            let key = crawled_stream.stream.key();
+            // a curated channel that also turned up in a crawl stays marked as curated
+            let curated = crawled_stream.stream.included_by == Inclusion::Curated
+                || fresh
+                    .get(&key)
+                    .is_some_and(|seen| seen.stream.included_by == Inclusion::Curated);
+            let is_fresher = fresh
+                .get(&key)
+                .is_none_or(|seen| crawled_stream.seen_at >= seen.seen_at);
+            if is_fresher {
+                fresh.insert(key.clone(), crawled_stream);
+            }
+            if let Some(seen) = fresh.get_mut(&key).filter(|_| curated) {
+                seen.stream.included_by = Inclusion::Curated;
+            }
+        }
+
+        let mut last_seen: HashMap<PlatformKey, Instant> = fresh
+            .iter()
+            .map(|(id, crawled_stream)| (id.clone(), crawled_stream.seen_at))
+            .collect();
+        let mut streams: Vec<LiveStream> = fresh.into_values().map(|c| c.stream).collect();
+
+        for (id, seen_at) in &self.last_seen {
+            if last_seen.contains_key(id) || seen_at.elapsed() > grace_period {
+                continue;
+            }
+            if let Some(stream) = self.get(id.0, &id.1) {
+                last_seen.insert(id.clone(), *seen_at);
+                streams.push(stream.clone());
+            }
+        }
+
+        streams.sort_by_key(|s| Reverse(s.viewer_count));
+
+        let mut snapshot = Self::new(version, streams, next_refresh_at, self.rules.clone());
+        snapshot.last_seen = last_seen;
+        snapshot
+    }
+
+    /// Rebuilds the listings under the current rules, keeping the streams as they are.
+    pub fn relist(&self) -> Self {
+        self.with_rules(self.rules.clone())
+    }
+
+    /// Rebuilds the listings under new rules, which later polls carry on using.
+    pub fn with_rules(&self, rules: Arc<ListingRules>) -> Self {
+        let mut snapshot = Self::new(
+            self.version,
+            self.streams.clone(),
+            self.next_refresh_at,
+            rules,
+        );
+        snapshot.refreshed_at = self.refreshed_at;
+        snapshot.last_seen = self.last_seen.clone();
+        snapshot
+    }
+
+    pub fn empty(rules: Arc<ListingRules>) -> Self {
+        Self::new(0, vec![], Instant::now(), rules)
+    }
+
+    /// Whether this snapshot was filled by a poll rather than created empty at startup.
+    pub fn is_polled(&self) -> bool {
+        self.version > 0
+    }
+
+    pub fn age(&self) -> Duration {
+        self.refreshed_at.elapsed()
+    }
+
+    /// Reads a snapshot saved by [`StreamsSnapshot::save`], keeping its original age.
+    pub fn load(path: &Path, rules: Arc<ListingRules>) -> io::Result<Self> {
+        let persisted: PersistedSnapshot = serde_json::from_slice(&fs::read(path)?)?;
+        let age = Duration::from_secs((unix_now() - persisted.refreshed_at).max(0) as u64);
+
+        let mut snapshot = Self::new(persisted.version, persisted.streams, Instant::now(), rules);
+        snapshot.refreshed_at = Instant::now()
+            .checked_sub(age)
+            .unwrap_or(snapshot.refreshed_at);
+        for seen_at in snapshot.last_seen.values_mut() {
+            *seen_at = snapshot.refreshed_at;
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot to `path`, replacing it atomically.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let persisted = PersistedSnapshot {
+            version: self.version,
+            refreshed_at: unix_now() - self.age().as_secs() as i64,
+            streams: self.streams.clone(),
+        };
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(&persisted)?)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Time left until the poller is expected to replace this snapshot.
+    pub fn max_age(&self) -> Duration {
+        self.next_refresh_at
+            .saturating_duration_since(Instant::now())
+    }
+}
+
+/// Holds the current snapshot; readers take a cheap reference while the poller swaps in the next.
+#[derive(Clone)]
+pub struct SnapshotStore {
+    current: Arc<ArcSwap<StreamsSnapshot>>,
+    /// Held while a snapshot is built from the current one, so that two updates racing
+    /// (a poll and a denylist change) can't drop each other's changes.
+    updating: Arc<Mutex<()>>,
+}
+
+impl SnapshotStore {
+    pub fn new(snapshot: StreamsSnapshot) -> Self {
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(snapshot)),
+            updating: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn load(&self) -> Arc<StreamsSnapshot> {
+        self.current.load_full()
+    }
+
+    /// Builds the next snapshot from the current one and swaps it in.
+    pub fn update(
+        &self,
+        next: impl FnOnce(&StreamsSnapshot) -> StreamsSnapshot,
+    ) -> Arc<StreamsSnapshot> {
+        let _updating = self.updating.lock().unwrap();
+        let snapshot = Arc::new(next(&self.current.load()));
+        self.current.store(snapshot.clone());
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::denylist::Denylist;
+
+    fn rules() -> Arc<ListingRules> {
+        Arc::new(ListingRules {
+            tags: HashMap::new(),
+            programming_game_ids: vec!["1469308723".to_owned()],
+            title_blocklist: vec![],
+            all_tags: HashMap::new(),
+            creators: CreatorRegistry::default(),
+            denylist: Arc::new(Denylist::new(None)),
+        })
+    }
+
+    fn stream(id: &str, viewer_count: u64) -> LiveStream {
+        LiveStream {
+            platform: Platform::Twitch,
+            id: id.to_owned(),
+            user_id: format!("user-{}", id),
+            user_login: format!("login_{}", id),
+            user_name: format!("Login_{}", id),
+            url: String::new(),
+            game_id: "1469308723".to_owned(),
+            game_name: "Software and Game Development".to_owned(),
+            title: "building things".to_owned(),
+            language: "en".to_owned(),
+            started_at: "2026-10-19T08:00:00Z".to_owned(),
+            thumbnail_url: String::new(),
+            viewer_count,
+            tag_ids: None,
+            r#type: "live".to_owned(),
+            included_by: Inclusion::Crawl,
+            creator_id: None,
+            simulcasts: vec![],
+        }
+    }
+
+    fn crawled(stream: LiveStream, seen_at: Instant) -> CrawledStream {
+        CrawledStream { stream, seen_at }
+    }
+
+    fn merge(previous: &StreamsSnapshot, crawl: Vec<CrawledStream>, grace: u64) -> StreamsSnapshot {
+        previous.merge(
+            previous.version + 1,
+            crawl,
+            Instant::now(),
+            Duration::from_secs(grace),
+        )
+    }
+
+    #[test]
+    fn merge_keeps_one_record_of_a_stream_seen_twice() {
+        let earlier = Instant::now() - Duration::from_secs(1);
+        let later = Instant::now();
+
+        // pages can come back in any order, the later fetch wins either way
+        for crawl in [
+            vec![
+                crawled(stream("1", 10), earlier),
+                crawled(stream("1", 20), later),
+            ],
+            vec![
+                crawled(stream("1", 20), later),
+                crawled(stream("1", 10), earlier),
+            ],
+        ] {
+            let snapshot = merge(&StreamsSnapshot::empty(rules()), crawl, 60);
+
+            assert_eq!(snapshot.streams.len(), 1);
+            assert_eq!(
+                snapshot.get(Platform::Twitch, "1").unwrap().viewer_count,
+                20
+            );
+        }
+    }
+
+    #[test]
+    fn merge_keeps_a_curated_stream_curated() {
+        let earlier = Instant::now() - Duration::from_secs(1);
+        let curated = LiveStream {
+            included_by: Inclusion::Curated,
+            ..stream("1", 10)
+        };
+
+        let crawl = vec![
+            crawled(curated, earlier),
+            crawled(stream("1", 20), Instant::now()),
+        ];
+        let snapshot = merge(&StreamsSnapshot::empty(rules()), crawl, 60);
+
+        let merged = snapshot.get(Platform::Twitch, "1").unwrap();
+        assert_eq!(merged.viewer_count, 20);
+        assert_eq!(merged.included_by, Inclusion::Curated);
+    }
+
+    #[test]
+    fn merge_carries_missed_streams_through_the_grace_period() {
+        let seen_at = Instant::now() - Duration::from_secs(2);
+        let previous = merge(
+            &StreamsSnapshot::empty(rules()),
+            vec![
+                crawled(stream("1", 10), seen_at),
+                crawled(stream("2", 30), seen_at),
+            ],
+            60,
+        );
+
+        let carried = merge(
+            &previous,
+            vec![crawled(stream("2", 40), Instant::now())],
+            60,
+        );
+        assert_eq!(carried.streams.len(), 2);
+        assert_eq!(carried.get(Platform::Twitch, "1").unwrap().viewer_count, 10);
+        // carried streams keep when they were last seen, so they still expire on time
+        assert_eq!(
+            carried.last_seen[&(Platform::Twitch, "1".to_owned())],
+            seen_at
+        );
+
+        let expired = merge(&carried, vec![crawled(stream("2", 40), Instant::now())], 1);
+        assert_eq!(expired.streams.len(), 1);
+        assert!(expired.get(Platform::Twitch, "1").is_none());
+    }
+}
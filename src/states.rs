@@ -1,34 +1,228 @@
 use crate::{
-    category::Category,
     clients::twitch::{get_token, Token},
+    config::Secret,
+    metrics::record_app_token_refresh,
+    poller::PollerStatus,
 };
-use rocket::info;
+use rocket::{
+    http::Status,
+    tokio::{
+        sync::{watch, Mutex as AsyncMutex},
+        time,
+    },
+};
+use serde::Serialize;
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
+use tracing::{error, info, warn};
+
+/// How many times startup tries to fetch the first app token before leaving it to the next
+/// request that needs one.
+const STARTUP_TOKEN_ATTEMPTS: u32 = 6;
+const STARTUP_MAX_BACKOFF: Duration = Duration::from_secs(30);
 
 pub struct GlobalConfig {
     pub client_id: String,
-    pub client_secret: String,
-    pub tags: HashMap<Category, String>,
-    pub token: Arc<Mutex<Token>>,
-    pub expired: Arc<Mutex<std::time::Instant>>,
-    pub all_tags: HashMap<String, String>,
+    pub app_token: Arc<AppToken>,
+    pub required_scopes: Vec<String>,
+    /// Other first-party client ids whose user tokens we accept.
+    pub allowed_client_ids: Vec<String>,
+    /// Callback registered with Twitch; the login routes are disabled without it.
+    pub redirect_uri: Option<String>,
+    pub login_scopes: Vec<String>,
+    /// Where the browser is sent once a login completes.
+    pub login_redirect: String,
+    /// Credential for the admin routes, which are disabled without it.
+    pub admin_token: Option<Secret>,
+    /// Readiness fails once the streams snapshot is older than this.
+    pub max_snapshot_age: Duration,
+    pub poller: Arc<PollerStatus>,
 }
 
 impl GlobalConfig {
-    pub fn fetch_access_token(&self) -> String {
-        let is_expired = std::time::Instant::now() >= *self.expired.lock().unwrap();
-
-        if is_expired {
-            info!("token expired at: {:?}", std::time::Instant::now());
-            let token_response = get_token(&self.client_id, &self.client_secret);
-            *self.expired.lock().unwrap() = std::time::Instant::now()
-                + std::time::Duration::from_secs(token_response.expires_in);
-            *self.token.lock().unwrap() = token_response;
+    pub fn is_accepted_client_id(&self, client_id: &str) -> bool {
+        self.client_id == client_id || self.allowed_client_ids.iter().any(|id| id == client_id)
+    }
+}
+
+/// How the first app token fetch, made at startup, went.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenFetch {
+    Pending,
+    Fetched,
+    /// Twitch turned down the client id or secret; nothing is retried until the secret is
+    /// rotated.
+    Rejected,
+    /// Twitch couldn't be reached in any of the startup attempts; the next request that needs
+    /// a token tries again.
+    Unreachable,
+}
+
+/// The app access token shared by the routes and the poller.
+pub struct AppToken {
+    client_id: String,
+    /// Held here rather than in [`GlobalConfig`] so that it can be rotated at runtime.
+    client_secret: Mutex<Secret>,
+    token: Mutex<Token>,
+    expired: Mutex<Instant>,
+    refresh_failing: AtomicBool,
+    /// Held while a refresh is in flight, so requests that find the token expired together
+    /// wait for one fetch instead of each making their own.
+    refreshing: AsyncMutex<()>,
+    fetch: watch::Sender<TokenFetch>,
+}
+
+impl AppToken {
+    /// Starts without a token; [`AppToken::fetch_at_startup`] fetches the first one.
+    pub fn new(client_id: String, client_secret: Secret) -> Self {
+        Self {
+            client_id,
+            client_secret: Mutex::new(client_secret),
+            token: Mutex::new(Token::default()),
+            expired: Mutex::new(Instant::now()),
+            refresh_failing: AtomicBool::new(false),
+            refreshing: AsyncMutex::new(()),
+            fetch: watch::channel(TokenFetch::Pending).0,
+        }
+    }
+
+    fn set_token(&self, token: Token) {
+        *self.expired.lock().unwrap() = Instant::now() + Duration::from_secs(token.expires_in);
+        *self.token.lock().unwrap() = token;
+        self.refresh_failing.store(false, Ordering::Relaxed);
+    }
+
+    /// Fetches the first token, retrying network failures and Twitch errors with backoff.
+    /// Rejected credentials aren't retried, they won't get any better.
+    pub async fn fetch_at_startup(&self) {
+        let mut backoff = Duration::from_secs(1);
+
+        for attempt in 1..=STARTUP_TOKEN_ATTEMPTS {
+            match get_token(&self.client_id, &self.client_secret()).await {
+                Ok(token) => {
+                    info!(
+                        expires_in = token.expires_in,
+                        attempt, "fetched twitch app token"
+                    );
+                    record_app_token_refresh(true);
+                    self.set_token(token);
+                    self.fetch.send_replace(TokenFetch::Fetched);
+                    return;
+                }
+                Err(status) if status.code == Status::Unauthorized.code => {
+                    record_app_token_refresh(false);
+                    self.refresh_failing.store(true, Ordering::Relaxed);
+                    self.fetch.send_replace(TokenFetch::Rejected);
+                    error!(
+                        client_id = %self.client_id,
+                        "twitch rejected twitch_client_id or twitch_client_secret; fix the config \
+                         or rotate the secret through the admin routes"
+                    );
+                    return;
+                }
+                Err(status) if attempt < STARTUP_TOKEN_ATTEMPTS => {
+                    warn!(
+                        %status,
+                        attempt,
+                        retry_in_secs = backoff.as_secs(),
+                        "failed to fetch twitch app token, retrying"
+                    );
+                    time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(STARTUP_MAX_BACKOFF);
+                }
+                Err(status) => {
+                    record_app_token_refresh(false);
+                    self.refresh_failing.store(true, Ordering::Relaxed);
+                    self.fetch.send_replace(TokenFetch::Unreachable);
+                    error!(
+                        %status,
+                        attempts = STARTUP_TOKEN_ATTEMPTS,
+                        "couldn't reach twitch for an app token, retrying when one is next needed"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Resolves once the startup fetch has finished, however it went.
+    pub async fn startup_fetched(&self) {
+        let mut fetch = self.fetch.subscribe();
+        while *fetch.borrow_and_update() == TokenFetch::Pending {
+            if fetch.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub fn startup_fetch(&self) -> TokenFetch {
+        *self.fetch.borrow()
+    }
+
+    fn needs_refresh(&self) -> bool {
+        let is_expired = Instant::now() >= *self.expired.lock().unwrap();
+        // the startup fetch is still going, or the credentials are known to be wrong
+        let can_refresh = !matches!(
+            self.startup_fetch(),
+            TokenFetch::Pending | TokenFetch::Rejected
+        );
+
+        is_expired && can_refresh
+    }
+
+    pub async fn fetch_access_token(&self) -> String {
+        if self.needs_refresh() {
+            let _refreshing = self.refreshing.lock().await;
+            // whoever held the lock may have refreshed it already
+            if self.needs_refresh() {
+                self.refresh().await;
+            }
         }
 
         self.token.lock().unwrap().access_token.clone()
     }
+
+    async fn refresh(&self) {
+        info!("app token expired, fetching a new one");
+        match get_token(&self.client_id, &self.client_secret()).await {
+            Ok(token_response) => {
+                record_app_token_refresh(true);
+                self.set_token(token_response);
+                self.fetch.send_replace(TokenFetch::Fetched);
+            }
+            Err(status) => {
+                // keep using the old token, the next request retries
+                record_app_token_refresh(false);
+                self.refresh_failing.store(true, Ordering::Relaxed);
+                warn!(%status, "app token refresh failed");
+            }
+        }
+    }
+
+    pub fn client_secret(&self) -> String {
+        self.client_secret.lock().unwrap().expose().to_owned()
+    }
+
+    /// Swaps in a new client secret, once a token has been fetched with it. The old secret
+    /// and token stay in use when the new secret is rejected.
+    pub async fn rotate_client_secret(&self, client_secret: String) -> Result<(), Status> {
+        let token_response = get_token(&self.client_id, &client_secret).await?;
+
+        self.set_token(token_response);
+        *self.client_secret.lock().unwrap() = client_secret.into();
+        self.fetch.send_replace(TokenFetch::Fetched);
+
+        info!("rotated the twitch client secret");
+        Ok(())
+    }
+
+    /// Whether the last attempt to replace an expired token failed.
+    pub fn is_refresh_failing(&self) -> bool {
+        self.refresh_failing.load(Ordering::Relaxed)
+    }
 }
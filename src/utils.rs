@@ -1,6 +1,6 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use rocket::info;
 use rocket::{http::Status, request::Request};
 use rocket::{
     response::{self, Responder, Response},
@@ -9,11 +9,17 @@ use rocket::{
 use serde::Serialize;
 
 use crate::category::Category;
-use crate::clients::twitch::TwitchStream;
+use crate::creators::CreatorRegistry;
+use crate::denylist::Denylist;
+use crate::fairings::compression::Encoding;
+use crate::guards::api_key::ApiKeys;
+use crate::providers::{Inclusion, LiveStream, Platform};
 
 pub struct JsonResponse<T> {
     data: T,
     status_code: Status,
+    etag: Option<String>,
+    max_age: Option<u64>,
 }
 
 impl<T> JsonResponse<T>
@@ -21,95 +27,175 @@ where
     T: Serialize,
 {
     pub fn new(data: T, status_code: Status) -> Self {
-        Self { data, status_code }
+        Self {
+            data,
+            status_code,
+            etag: None,
+            max_age: None,
+        }
+    }
+
+    /// Sets a strong `ETag`, answering a matching `If-None-Match` with 304.
+    pub fn with_etag(mut self, etag: String) -> Self {
+        self.etag = Some(format!("\"{}\"", etag));
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: u64) -> Self {
+        self.max_age = Some(max_age);
+        self
     }
 }
 
+fn is_not_modified(request: &Request<'_>, etag: &str) -> bool {
+    let weak = |tag: &str| Encoding::strip_etag_suffix(tag.trim().trim_start_matches("W/"));
+
+    request
+        .headers()
+        .get("If-None-Match")
+        .flat_map(|header| header.split(','))
+        .any(|tag| tag.trim() == "*" || weak(tag) == weak(etag))
+}
+
 #[rocket::async_trait]
 impl<'r, T: Serialize> Responder<'r, 'static> for JsonResponse<T> {
     fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
-        Response::build_from(Json(self.data).respond_to(request).unwrap())
-            .status(self.status_code)
-            .ok()
+        let not_modified = self.status_code == Status::Ok
+            && self
+                .etag
+                .as_deref()
+                .is_some_and(|etag| is_not_modified(request, etag));
+
+        let mut response = match not_modified {
+            true => Response::build().status(Status::NotModified).finalize(),
+            false => Response::build_from(Json(self.data).respond_to(request).unwrap())
+                .status(self.status_code)
+                .finalize(),
+        };
+
+        if let Some(etag) = self.etag {
+            response.set_raw_header("ETag", etag);
+        }
+
+        if let Some(max_age) = self.max_age {
+            // shared caches would otherwise hand a keyed response to requests without a key
+            let keyed = request
+                .rocket()
+                .state::<ApiKeys>()
+                .is_some_and(|api_keys| api_keys.required);
+            let visibility = match keyed {
+                true => "private",
+                false => "public",
+            };
+            response.set_raw_header(
+                "Cache-Control",
+                format!("{}, max-age={}", visibility, max_age),
+            );
+        }
+
+        Ok(response)
     }
 }
 
-const TITLE_BLACKLIST: &[&str] = &["minecraft", "fortnite", "pokemon"];
-
-pub fn filter_by_category(
-    streams: Vec<TwitchStream>,
-    category_tag: &str,
-    all_tags: &HashMap<String, String>,
-) -> Vec<TwitchStream> {
-    streams
-        .into_iter()
-        .filter(|stream| {
-            // let is_matched_tag = match &stream.tag_ids {
-            //     Some(tags) => tags.iter().any(|id| id.eq(category_tag)),
-            //     None => false,
-            // };
-
-            let is_blacklist = TITLE_BLACKLIST
-                .iter()
-                .any(|blacklist| stream.title.to_lowercase().contains(blacklist));
-
-            !is_blacklist
-        })
-        .map(|mut s| {
-            s.tag_ids = {
-                if s.tag_ids.is_none() {
-                    Some(vec!["programming".to_owned()])
-                } else {
-                    Some(
-                        s.tag_ids
-                            .unwrap()
-                            .into_iter()
-                            .map(|ids| all_tags.get(&ids).unwrap().to_owned())
-                            .collect(),
-                    )
-                }
-            };
-            s
-        })
-        .collect()
+/// What the snapshot builder needs to work out which listings a stream appears in.
+#[derive(Clone)]
+pub struct ListingRules {
+    pub tags: HashMap<Category, String>,
+    /// Twitch games whose streams make the unfiltered listing.
+    pub programming_game_ids: Vec<String>,
+    /// Lowercased words that keep a stream out of the listings when its title contains one.
+    pub title_blocklist: Vec<String>,
+    pub all_tags: HashMap<String, String>,
+    pub creators: CreatorRegistry,
+    /// Shared with the admin routes, which relist the snapshot after changing it.
+    pub denylist: Arc<Denylist>,
 }
 
-pub fn filter_all_programming_streams(
-    streams: Vec<TwitchStream>,
-    tag_ids: &HashMap<Category, String>,
-    all_tags: &HashMap<String, String>,
-) -> Vec<TwitchStream> {
-    let tag_id_vals: Vec<&String> = tag_ids.values().collect();
-    streams
-        .into_iter()
-        .filter(|stream| {
-            // let is_matched_tag = match &stream.tag_ids {
-            //     Some(tags) => tags.iter().any(|id| tag_id_vals.contains(&id)),
-            //     None => false,
-            // };
-            let is_blacklist = TITLE_BLACKLIST
-                .iter()
-                .any(|blacklist| stream.title.to_lowercase().contains(blacklist));
-
-            (stream.game_id == "1469308723") && !is_blacklist
-        })
-        .map(|mut s| {
-            info!("{:#?}", s.tag_ids);
-            s.tag_ids = {
-                if s.tag_ids.is_none() {
-                    Some(vec!["programming".to_owned()])
-                } else {
-                    Some(
-                        s.tag_ids
-                            .unwrap()
-                            .into_iter()
-                            // .filter(|ids| all_tags.get(ids).is_some())
-                            .map(|ids| all_tags.get(&ids).unwrap().to_owned())
-                            .collect(),
-                    )
-                }
-            };
-            s
-        })
-        .collect()
+impl ListingRules {
+    pub fn is_blacklisted(&self, stream: &LiveStream) -> bool {
+        let title = stream.title.to_lowercase();
+        self.title_blocklist
+            .iter()
+            .any(|blacklist| title.contains(blacklist.as_str()))
+    }
+
+    pub fn is_denied(&self, stream: &LiveStream) -> bool {
+        self.denylist.is_denied(stream.platform, &stream.user_id)
+    }
+
+    /// Distinguishes listings built under different rules, for their `ETag`.
+    pub fn fingerprint(&self) -> String {
+        let mut tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|(category, tag_id)| format!("{:?}={}", category, tag_id))
+            .collect();
+        tags.sort();
+
+        let mut tag_names: Vec<String> = self
+            .all_tags
+            .iter()
+            .map(|(tag_id, name)| format!("{}={}", tag_id, name))
+            .collect();
+        tag_names.sort();
+
+        format!(
+            "{};{};{};{};{};{}",
+            tags.join(","),
+            self.programming_game_ids.join(","),
+            self.title_blocklist.join(","),
+            tag_names.join(","),
+            self.creators.fingerprint(),
+            self.denylist.fingerprint()
+        )
+    }
+
+    /// Whether a stream belongs in the unfiltered listing. Curated channels always do.
+    pub fn is_programming(&self, stream: &LiveStream) -> bool {
+        if stream.included_by == Inclusion::Curated {
+            return true;
+        }
+
+        match stream.platform {
+            Platform::Twitch => self.programming_game_ids.contains(&stream.game_id),
+            // youtube has no programming category, its searches and channels are picked for it
+            Platform::Youtube => true,
+            // only the software development category is crawled
+            Platform::Kick => true,
+        }
+    }
+
+    /// Whether a stream belongs in the listing for `category`.
+    pub fn matches_category(&self, _stream: &LiveStream, category: &Category) -> bool {
+        // Twitch stopped returning tag ids, so every stream matches until categories are
+        // worked out some other way
+        // match &stream.tag_ids {
+        //     Some(tags) => tags.iter().any(|id| Some(id) == self.tags.get(category)),
+        //     None => false,
+        // }
+        self.tags.contains_key(category)
+    }
+
+    /// Replaces the stream's tag ids with tag names, as listings show them.
+    pub fn with_tag_names(&self, mut stream: LiveStream) -> LiveStream {
+        stream.tag_ids = match stream.tag_ids {
+            None => Some(vec!["programming".to_owned()]),
+            Some(tag_ids) => Some(
+                tag_ids
+                    .iter()
+                    .filter_map(|id| self.all_tags.get(id).cloned())
+                    .collect(),
+            ),
+        };
+        stream
+    }
+
+    /// Tags the stream with the creator its channel is registered to.
+    pub fn with_creator(&self, mut stream: LiveStream) -> LiveStream {
+        stream.creator_id = self
+            .creators
+            .for_account(stream.platform, &stream.user_login)
+            .map(|creator| creator.id.clone());
+        stream
+    }
 }